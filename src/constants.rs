@@ -59,6 +59,8 @@ pub enum IndexTag {
     RPMTAG_SHA256HEADER = RPMTAG_SIG_BASE + 17,
     RPMTAG_VERITYSIGNATURES = RPMTAG_SIG_BASE + 20,
     RPMTAG_VERITYSIGNATUREALGO = RPMTAG_SIG_BASE + 21,
+    RPMTAG_OPENPGP = RPMTAG_SIG_BASE + 22,
+    RPMTAG_SHA3_256HEADER = RPMTAG_SIG_BASE + 23,
 
     RPMTAG_NAME = 1000,
     RPMTAG_VERSION = 1001,
@@ -430,6 +432,14 @@ pub enum IndexSignatureTag {
     /// Index Records and Header store, stored as a hex-encoded string.
     RPMSIGTAG_SHA256 = IndexTag::RPMTAG_SHA256HEADER as u32,
 
+    /// OpenPGP signatures of the Header section, as introduced by rpm 6. Unlike the other signature
+    /// tags, it can hold any number of signatures, each a base64 encoded Signature Packet.
+    RPMSIGTAG_OPENPGP = IndexTag::RPMTAG_OPENPGP as u32,
+
+    /// The SHA3-256 checksum of the entire Header Section, stored as a hex-encoded string. Defined by
+    /// rpm 6.
+    RPMSIGTAG_SHA3_256 = IndexTag::RPMTAG_SHA3_256HEADER as u32,
+
     /// A silly tag for a date.
     RPMTAG_INSTALLTIME = IndexTag::RPMTAG_INSTALLTIME as u32,
 }
@@ -560,7 +570,7 @@ bitflags! {
         const DONOTUSE = 1 << 2;  // %%donotuse
         const MISSINGOK = 1 << 3;  // %%config(missingok)
         const NOREPLACE = 1 << 4;  // %%config(noreplace)
-        // const SPECFILE = 1 << 5;  // first file in SRPM?
+        const SPECFILE = 1 << 5;  // the spec file of a source package
         const GHOST = 1 << 6;  // %%ghost
         const LICENSE = 1 << 7;  // %%license
         const README = 1 << 8;  // %%readme
@@ -582,6 +592,26 @@ pub enum DigestAlgorithm {
     Sha2_224 = 11,
 }
 
+impl DigestAlgorithm {
+    /// A hasher computing digests with this algorithm
+    pub(crate) fn hasher(&self) -> Box<dyn digest::DynDigest> {
+        match self {
+            DigestAlgorithm::Md5 => Box::<md5::Md5>::default(),
+            DigestAlgorithm::Sha2_256 => Box::<sha2::Sha256>::default(),
+            DigestAlgorithm::Sha2_384 => Box::<sha2::Sha384>::default(),
+            DigestAlgorithm::Sha2_512 => Box::<sha2::Sha512>::default(),
+            DigestAlgorithm::Sha2_224 => Box::<sha2::Sha224>::default(),
+        }
+    }
+
+    /// The hex encoded digest of the data
+    pub(crate) fn hex_digest(&self, data: &[u8]) -> String {
+        let mut hasher = self.hasher();
+        hasher.update(data);
+        hex::encode(hasher.finalize())
+    }
+}
+
 /// Index tag values for the %prein scriptlet,
 pub(crate) const PREIN_TAGS: ScriptletIndexTags = (
     IndexTag::RPMTAG_PREIN,
@@ -637,3 +667,10 @@ pub(crate) const POSTUNTRANS_TAGS: ScriptletIndexTags = (
     IndexTag::RPMTAG_POSTUNTRANSFLAGS,
     IndexTag::RPMTAG_POSTUNTRANSPROG,
 );
+
+/// Index tag values for the %verifyscript scriptlet,
+pub(crate) const VERIFYSCRIPT_TAGS: ScriptletIndexTags = (
+    IndexTag::RPMTAG_VERIFYSCRIPT,
+    IndexTag::RPMTAG_VERIFYSCRIPTFLAGS,
+    IndexTag::RPMTAG_VERIFYSCRIPTPROG,
+);
@@ -2,7 +2,7 @@ use std::{io, str::Utf8Error};
 
 use thiserror::Error;
 
-use crate::{DigestAlgorithm, TimestampError};
+use crate::{DigestAlgorithm, RpmVersion, Target, TimestampError};
 
 #[derive(Error, Debug)]
 #[non_exhaustive]
@@ -13,6 +13,9 @@ pub enum Error {
     #[error(transparent)]
     Hex(#[from] hex::FromHexError),
 
+    #[error(transparent)]
+    Base64(#[from] base64::DecodeError),
+
     #[error("{0}")]
     Nom(String),
     #[error(
@@ -58,6 +61,9 @@ pub enum Error {
     #[error("signature packet not found in what is supposed to be a signature")]
     NoSignatureFound,
 
+    #[error("no public key found in what is supposed to be key data")]
+    NoPublicKeyFound,
+
     #[cfg(feature = "signature-pgp")]
     #[error("error creating signature: {0}")]
     SignError(#[source] pgp::errors::Error),
@@ -91,9 +97,22 @@ pub enum Error {
     #[error("unable to find key with key-ref: {key_ref}")]
     KeyNotFoundError { key_ref: String },
 
-    #[error("unknown compressor type {0} - supported types: gzip, zstd, xz, bzip2 and none")]
+    #[error("unknown compressor type {0} - supported types: gzip, zstd, xz, bzip2, lzma and none")]
     UnknownCompressorType(String),
 
+    #[error("invalid payload flags {0:?}")]
+    InvalidPayloadFlags(String),
+
+    #[error("invalid rpm version {0:?}")]
+    InvalidRpmVersion(String),
+
+    #[error("{feature} requires rpm {required}, which is newer than the target {target}")]
+    UnsupportedByTarget {
+        feature: String,
+        required: RpmVersion,
+        target: Target,
+    },
+
     #[error("unsupported digest algorithm {0:?}")]
     UnsupportedDigestAlgorithm(DigestAlgorithm),
 
@@ -109,6 +128,35 @@ pub enum Error {
 
     #[error("{0}")]
     InvalidFileCaps(String),
+
+    #[error("invalid epoch-version-release string {0}")]
+    InvalidEvr(String),
+
+    #[error("invalid name-epoch-version-release-arch string {0}")]
+    InvalidNevra(String),
+
+    #[cfg(feature = "repodata")]
+    #[error("repository metadata xml error: {0}")]
+    RepodataXml(
+        #[from]
+        #[source]
+        quick_xml::Error,
+    ),
+
+    #[error("invalid repository metadata: {0}")]
+    InvalidRepodata(String),
+
+    #[error("checksum of repository metadata file {path} does not match repomd.xml")]
+    RepodataChecksumMismatch { path: String },
+
+    #[error("invalid source package: {0}")]
+    InvalidSourcePackage(&'static str),
+
+    #[error("the {0} tag describes the structure of the header and can't be edited")]
+    UneditableTag(String),
+
+    #[error("the content of file {0} is missing from the payload")]
+    MissingPayloadFile(String),
 }
 
 impl From<nom::Err<(&[u8], nom::error::ErrorKind)>> for Error {
@@ -1,4 +1,4 @@
-use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::convert::TryInto;
 
 use std::fs;
@@ -13,7 +13,7 @@ use std::path::{Path, PathBuf};
 
 use digest::Digest;
 
-use super::compressor::Compressor;
+use super::compressor::{decompress_stream, Compressor};
 use super::headers::*;
 use super::Lead;
 use crate::errors::*;
@@ -24,7 +24,7 @@ use crate::signature;
 
 use crate::Package;
 use crate::PackageMetadata;
-use crate::{CompressionType, CompressionWithLevel, Digests};
+use crate::{CompressionOptions, Digests, RpmVersion, Target};
 
 #[cfg(unix)]
 fn file_mode(file: &fs::File) -> Result<u32, Error> {
@@ -40,7 +40,7 @@ fn file_mode(_file: &fs::File) -> Result<u32, Error> {
 #[derive(Default)]
 pub struct PackageBuilder {
     name: String,
-    epoch: u32,
+    epoch: Option<u32>,
     version: String,
     license: String,
     arch: String,
@@ -63,6 +63,7 @@ pub struct PackageBuilder {
     suggests: Vec<Dependency>,
     enhances: Vec<Dependency>,
     supplements: Vec<Dependency>,
+    order_with_requires: Vec<Dependency>,
 
     pre_inst_script: Option<Scriptlet>,
     post_inst_script: Option<Scriptlet>,
@@ -79,7 +80,10 @@ pub struct PackageBuilder {
     changelog_names: Vec<String>,
     changelog_entries: Vec<String>,
     changelog_times: Vec<Timestamp>,
-    compression: CompressionWithLevel,
+    compression: CompressionOptions,
+    file_digest_algorithm: Option<DigestAlgorithm>,
+    payload_digest_algorithm: Option<DigestAlgorithm>,
+    target: Option<Target>,
 
     vendor: Option<String>,
     packager: Option<String>,
@@ -87,9 +91,32 @@ pub struct PackageBuilder {
     url: Option<String>,
     vcs: Option<String>,
     cookie: Option<String>,
+    source_rpm: Option<String>,
+    dist_url: Option<String>,
+    distribution: Option<String>,
+    disttag: Option<String>,
+    bug_url: Option<String>,
+    platform: Option<String>,
+    optflags: Option<String>,
+    modularity_label: Option<String>,
+    source_license: Option<String>,
 
     source_date: Option<Timestamp>,
     build_host: Option<String>,
+
+    is_source: bool,
+    spec: Option<String>,
+    sources: Vec<SourceEntry>,
+    patches: Vec<SourceEntry>,
+    build_requires: Vec<Dependency>,
+    build_conflicts: Vec<Dependency>,
+}
+
+/// A `SourceN` or `PatchN` of a source package.
+struct SourceEntry {
+    name: String,
+    // whether the file is contained in the package, i.e. not listed in `NoSource` / `NoPatch`
+    included: bool,
 }
 
 impl PackageBuilder {
@@ -114,7 +141,7 @@ impl PackageBuilder {
     pub fn new(name: &str, version: &str, license: &str, arch: &str, summary: &str) -> Self {
         Self {
             name: name.to_string(),
-            epoch: 0,
+            epoch: Some(0),
             version: version.to_string(),
             license: license.to_string(),
             arch: arch.to_string(),
@@ -124,6 +151,324 @@ impl PackageBuilder {
         }
     }
 
+    /// Create a new source package (SRPM), providing the required metadata.
+    ///
+    /// A source package contains the spec file, which must be added with
+    /// [`PackageBuilder::with_spec_file`], and the sources and patches needed to build the binary
+    /// packages. Its architecture is `src`, or `nosrc` if some sources or patches are left out of
+    /// the package.
+    ///
+    /// ```
+    /// # fn foo() -> Result<(), Box<dyn std::error::Error>> {
+    /// let pkg = rpm::PackageBuilder::new_source("foo", "1.0.0", "Apache-2.0", "some baz package")
+    ///     .with_spec_file("./foo.spec")?
+    ///     .with_source_file("./foo-1.0.0.tar.gz")?
+    ///     .with_patch_file("./fix-build.patch")?
+    ///     .build_requires(rpm::Dependency::any("gcc"))
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_source(name: &str, version: &str, license: &str, summary: &str) -> Self {
+        Self {
+            is_source: true,
+            ..Self::new(name, version, license, "src", summary)
+        }
+    }
+
+    /// Create a builder from an existing package, e.g. to rebuild it with a few changes.
+    ///
+    /// All the metadata, dependencies, scriptlets, changelog entries and files, including their
+    /// content from the payload, are taken over. Dependencies which the builder generates by
+    /// itself, like the package's self-provides and the `rpmlib()` requirements of the payload,
+    /// are regenerated instead.
+    ///
+    /// An error is returned if the content of a regular file is missing from the payload.
+    ///
+    /// ```
+    /// # fn foo() -> Result<(), Box<dyn std::error::Error>> {
+    /// let package = rpm::Package::open("foo-1.0.0-1.x86_64.rpm")?;
+    /// let rebuilt = rpm::PackageBuilder::from_package(&package)?
+    ///     .release("2")
+    ///     .compression(rpm::CompressionType::Zstd)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_package(package: &Package) -> Result<Self, Error> {
+        let metadata = &package.metadata;
+        let optional = |value: Result<&str, Error>| match value {
+            Ok(value) => Ok(Some(value.to_owned())),
+            Err(Error::TagNotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        };
+        let scriptlet = |value: Result<Scriptlet, Error>| match value {
+            Ok(value) => Ok(Some(value)),
+            Err(Error::TagNotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        };
+
+        let name = metadata.get_name()?.to_owned();
+        let arch = metadata.get_arch()?.to_owned();
+        let is_source = metadata.is_source_package();
+        let file_entries = metadata.get_file_entries()?;
+
+        // trust the payload over the header for the compression type, and fall back to the
+        // default settings if the header doesn't describe the payload
+        let detected_compression = package.detect_payload_compressor()?;
+        let compression = match metadata.get_payload_compression() {
+            Ok(compression) if compression.compression_type() == detected_compression => {
+                compression
+            }
+            _ => detected_compression.into(),
+        };
+
+        let mut builder = Self {
+            name: name.clone(),
+            // keep packages without an epoch without one, rather than giving them epoch 0
+            epoch: match metadata.get_epoch() {
+                Err(Error::TagNotFound(_)) => None,
+                epoch => Some(epoch?),
+            },
+            version: metadata.get_version()?.to_owned(),
+            release: metadata.get_release()?.to_owned(),
+            license: metadata.get_license()?.to_owned(),
+            // the header of a source package records the architecture it was built on, but the
+            // package itself is a `src` (or `nosrc`) package
+            arch: if is_source {
+                "src".to_owned()
+            } else {
+                arch.clone()
+            },
+            summary: metadata.get_summary()?.to_owned(),
+            desc: optional(metadata.get_description())?,
+            compression,
+            // rpm doesn't record the file digest algorithm if it is the default md5
+            file_digest_algorithm: match metadata.get_file_digest_algorithm() {
+                Err(Error::TagNotFound(_)) => Some(DigestAlgorithm::Md5),
+                algorithm => Some(algorithm?),
+            },
+            payload_digest_algorithm: match metadata.get_payload_digest_algorithm() {
+                Err(Error::TagNotFound(_)) => None,
+                algorithm => Some(algorithm?),
+            },
+
+            pre_inst_script: scriptlet(metadata.get_pre_install_script())?,
+            post_inst_script: scriptlet(metadata.get_post_install_script())?,
+            pre_uninst_script: scriptlet(metadata.get_pre_uninstall_script())?,
+            post_uninst_script: scriptlet(metadata.get_post_uninstall_script())?,
+            pre_trans_script: scriptlet(metadata.get_pre_trans_script())?,
+            post_trans_script: scriptlet(metadata.get_post_trans_script())?,
+            pre_untrans_script: scriptlet(metadata.get_pre_untrans_script())?,
+            post_untrans_script: scriptlet(metadata.get_post_untrans_script())?,
+            verify_script: scriptlet(metadata.get_verify_script())?,
+
+            vendor: optional(metadata.get_vendor())?,
+            packager: optional(metadata.get_packager())?,
+            group: optional(metadata.get_group())?.filter(|group| group != "Unspecified"),
+            url: optional(metadata.get_url())?,
+            vcs: optional(metadata.get_vcs())?,
+            cookie: optional(metadata.get_cookie())?,
+            build_host: optional(metadata.get_build_host())?,
+            source_rpm: optional(metadata.get_source_rpm())?.filter(|rpm| rpm != "(none)"),
+            dist_url: optional(metadata.get_dist_url())?,
+            distribution: optional(metadata.get_distribution())?,
+            disttag: optional(metadata.get_disttag())?,
+            bug_url: optional(metadata.get_bug_url())?,
+            platform: optional(metadata.get_platform())?,
+            optflags: optional(metadata.get_optflags())?,
+            modularity_label: optional(metadata.get_modularity_label())?,
+            source_license: optional(metadata.get_source_license())?,
+
+            is_source,
+            ..Default::default()
+        };
+
+        for entry in metadata.get_changelog_entries()? {
+            builder.changelog_names.push(entry.name);
+            builder.changelog_entries.push(entry.description);
+            builder.changelog_times.push(Timestamp(
+                entry
+                    .timestamp
+                    .try_into()
+                    .map_err(|_| Error::TimestampConv(crate::TimestampError::Overflow))?,
+            ));
+        }
+
+        // leave out the dependencies the builder generates by itself
+        let self_provides = [name.clone(), format!("{}({})", name, arch)];
+        let generated_rpmlib = [
+            "CompressedFileNames",
+            "FileDigests",
+            "PayloadFilesHavePrefix",
+            "PayloadIsZstd",
+            "PayloadIsXz",
+            "PayloadIsLzma",
+            "PayloadIsBzip2",
+            "FileCaps",
+            "RichDependencies",
+            "TildeInVersions",
+            "CaretInVersions",
+        ]
+        .map(|feature| format!("rpmlib({})", feature));
+        let mut generated_recommends = Vec::new();
+        for entry in &file_entries {
+            if entry.ownership.user != "root" {
+                generated_recommends.push(Dependency::user(&entry.ownership.user));
+            }
+            if entry.ownership.group != "root" {
+                generated_recommends.push(Dependency::group(&entry.ownership.group));
+            }
+        }
+
+        builder.provides = metadata.get_provides()?;
+        builder
+            .provides
+            .retain(|d| !(d.flags == DependencyFlags::EQUAL && self_provides.contains(&d.name)));
+        let mut requires = metadata.get_requires()?;
+        requires.retain(|d| !generated_rpmlib.contains(&d.name));
+        let conflicts = metadata.get_conflicts()?;
+        if is_source {
+            builder.build_requires = requires;
+            builder.build_conflicts = conflicts;
+        } else {
+            builder.requires = requires;
+            builder.conflicts = conflicts;
+        }
+        builder.obsoletes = metadata.get_obsoletes()?;
+        builder.recommends = metadata.get_recommends()?;
+        builder
+            .recommends
+            .retain(|d| !generated_recommends.contains(d));
+        builder.suggests = metadata.get_suggests()?;
+        builder.enhances = metadata.get_enhances()?;
+        builder.supplements = metadata.get_supplements()?;
+        builder.order_with_requires = metadata.get_order_with_requires()?;
+
+        // the content of the files is taken from the payload
+        let mut contents = HashMap::new();
+        let mut archive = decompress_stream(
+            package.detect_payload_compressor()?,
+            package.content.as_slice(),
+        )?;
+        loop {
+            let mut reader = cpio::NewcReader::new(archive)?;
+            if reader.entry().is_trailer() {
+                break;
+            }
+            let path = reader.entry().name().trim_start_matches('.').to_owned();
+            let path = if is_source || path.starts_with('/') {
+                path
+            } else {
+                format!("/{}", path)
+            };
+            let mut content = Vec::new();
+            reader.read_to_end(&mut content)?;
+            contents.insert(path, content);
+            archive = reader.finish()?;
+        }
+
+        // hardlinked files share a device and inode number, and a newc archive only stores their
+        // content with the last link, so the other links are empty in the payload
+        let file_ids: Vec<Option<(u32, u32)>> = match (
+            metadata
+                .header
+                .get_entry_data_as_u32_array(IndexTag::RPMTAG_FILEDEVICES),
+            metadata
+                .header
+                .get_entry_data_as_u32_array(IndexTag::RPMTAG_FILEINODES),
+        ) {
+            (Ok(devices), Ok(inodes))
+                if devices.len() == file_entries.len() && inodes.len() == file_entries.len() =>
+            {
+                devices.into_iter().zip(inodes).map(Some).collect()
+            }
+            _ => vec![None; file_entries.len()],
+        };
+        let mut link_counts: HashMap<(u32, u32), usize> = HashMap::new();
+        for file_id in file_ids.iter().flatten() {
+            *link_counts.entry(*file_id).or_default() += 1;
+        }
+        let mut linked_contents = HashMap::new();
+        for (entry, file_id) in file_entries.iter().zip(&file_ids) {
+            let Some(file_id) = file_id.filter(|id| link_counts[id] > 1) else {
+                continue;
+            };
+            match contents.get(entry.path.to_string_lossy().as_ref()) {
+                Some(content) if !content.is_empty() => {
+                    linked_contents.insert(file_id, content.clone());
+                }
+                _ => {}
+            }
+        }
+
+        for (entry, file_id) in file_entries.into_iter().zip(file_ids) {
+            let path = entry.path.to_string_lossy().into_owned();
+            let content = match contents.remove(&path) {
+                Some(content) if content.is_empty() => file_id
+                    .and_then(|id| linked_contents.get(&id).cloned())
+                    .unwrap_or(content),
+                Some(content) => content,
+                // ghost files are not part of the payload, and directories and symlinks have no
+                // content of their own
+                None if entry.flags.contains(FileFlags::GHOST)
+                    || matches!(
+                        entry.mode,
+                        FileMode::Dir { .. } | FileMode::SymbolicLink { .. }
+                    ) =>
+                {
+                    Vec::new()
+                }
+                None => return Err(Error::MissingPayloadFile(path)),
+            };
+            if is_source {
+                if entry.flags.contains(FileFlags::SPECFILE) {
+                    builder.spec = Some(String::from_utf8_lossy(&content).into_owned());
+                }
+                builder.add_source_content(
+                    path,
+                    content,
+                    entry.mode,
+                    entry.modified_at,
+                    entry.flags,
+                )?;
+            } else {
+                let options = FileOptions {
+                    destination: path,
+                    user: entry.ownership.user,
+                    group: entry.ownership.group,
+                    symlink: entry.linkto,
+                    mode: entry.mode,
+                    flag: entry.flags,
+                    inherit_permissions: false,
+                    caps: entry
+                        .caps
+                        .filter(|caps| !caps.is_empty())
+                        .map(|caps| caps.parse())
+                        .transpose()?,
+                };
+                builder.add_data(content, entry.modified_at, options)?;
+            }
+        }
+
+        if is_source {
+            if let Ok(spec) = metadata.get_spec() {
+                builder.spec = Some(spec.to_owned());
+            }
+            for (sources, tag_sources) in [
+                (&mut builder.sources, metadata.get_sources()?),
+                (&mut builder.patches, metadata.get_patches()?),
+            ] {
+                sources.extend(tag_sources.into_iter().map(|source| SourceEntry {
+                    name: source.name,
+                    included: !source.excluded,
+                }));
+            }
+        }
+
+        Ok(builder)
+    }
+
     /// Set the package epoch.
     ///
     /// The main scenario in which this is used is if the version numbering scheme of the packaged
@@ -135,7 +480,7 @@ impl PackageBuilder {
     /// However, because of this, the epoch of a package must never decrease, and shouldn't be set
     /// unless required.
     pub fn epoch(mut self, epoch: u32) -> Self {
-        self.epoch = epoch;
+        self.epoch = Some(epoch);
         self
     }
 
@@ -198,6 +543,66 @@ impl PackageBuilder {
         self
     }
 
+    /// Set the file name of the source package this package was built from, e.g.
+    /// `foo-1.0-1.src.rpm`.
+    ///
+    /// If this is not set, the source package of binary packages is `(none)`. It is not written
+    /// for source packages.
+    pub fn source_rpm(mut self, content: impl Into<String>) -> Self {
+        self.source_rpm = Some(content.into());
+        self
+    }
+
+    /// Set the URL the package can be found at in the distribution, e.g. the build system
+    /// (`%{disturl}`)
+    pub fn dist_url(mut self, content: impl Into<String>) -> Self {
+        self.dist_url = Some(content.into());
+        self
+    }
+
+    /// Set the name of the distribution this package is part of, e.g. `Fedora Project`
+    pub fn distribution(mut self, content: impl Into<String>) -> Self {
+        self.distribution = Some(content.into());
+        self
+    }
+
+    /// Set the distribution tag (`%{disttag}`), an identifier of the distribution release,
+    /// e.g. `fc39`
+    pub fn disttag(mut self, content: impl Into<String>) -> Self {
+        self.disttag = Some(content.into());
+        self
+    }
+
+    /// Set the URL bugs of the package should be reported to
+    pub fn bug_url(mut self, content: impl Into<String>) -> Self {
+        self.bug_url = Some(content.into());
+        self
+    }
+
+    /// Set the platform the package was built for, e.g. `x86_64-redhat-linux-gnu`
+    pub fn platform(mut self, content: impl Into<String>) -> Self {
+        self.platform = Some(content.into());
+        self
+    }
+
+    /// Set the compiler flags the packaged software was built with (`%{optflags}`)
+    pub fn optflags(mut self, content: impl Into<String>) -> Self {
+        self.optflags = Some(content.into());
+        self
+    }
+
+    /// Set the modularity label of the package, for packages which are part of a module stream
+    pub fn modularity_label(mut self, content: impl Into<String>) -> Self {
+        self.modularity_label = Some(content.into());
+        self
+    }
+
+    /// Set the license of the source package, if it differs from the license of this package
+    pub fn source_license(mut self, content: impl Into<String>) -> Self {
+        self.source_license = Some(content.into());
+        self
+    }
+
     /// Define the name of the build host.
     ///
     /// Commonly used in conjunction with the `gethostname` crate.
@@ -276,13 +681,74 @@ impl PackageBuilder {
     /// For Xz compression, the expected range is 0 to 9, with a default value of 9.
     /// For Zstd compression, the expected range is 1 to 22, with a default value of 19.
     ///
+    /// To speed up compressing large payloads, the compression can be spread over several
+    /// worker threads. This is recorded in `RPMTAG_PAYLOADFLAGS` like rpm does, e.g. `19T8`.
+    ///
+    /// ```
+    /// # fn foo() -> Result<(), Box<dyn std::error::Error>> {
+    ///
+    /// let pkg = rpm::PackageBuilder::new("foo", "1.0.0", "MIT", "x86_64", "some baz package")
+    ///     .compression(rpm::CompressionWithLevel::Zstd(19).threads(8))
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
     /// If this method is not called, the payload will be Gzip compressed by default. This may
     /// change in future versions of the library.
-    pub fn compression(mut self, comp: impl Into<CompressionWithLevel>) -> Self {
+    pub fn compression(mut self, comp: impl Into<CompressionOptions>) -> Self {
         self.compression = comp.into();
         self
     }
 
+    /// Set the digest algorithm used for the digests of the files in the package. SHA-256 is
+    /// used by default.
+    ///
+    /// MD5 is only useful for targeting very old versions of rpm (before 4.6), which don't
+    /// support anything else.
+    ///
+    /// ```
+    /// # fn foo() -> Result<(), Box<dyn std::error::Error>> {
+    /// let pkg = rpm::PackageBuilder::new("foo", "1.0.0", "MIT", "x86_64", "some baz package")
+    ///     .file_digest_algorithm(rpm::DigestAlgorithm::Sha2_512)
+    ///     .payload_digest_algorithm(rpm::DigestAlgorithm::Sha2_512)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn file_digest_algorithm(mut self, algorithm: DigestAlgorithm) -> Self {
+        self.file_digest_algorithm = Some(algorithm);
+        self
+    }
+
+    /// Set the digest algorithm used for the digests of the payload, `RPMTAG_PAYLOADDIGEST` and
+    /// `RPMTAG_PAYLOADDIGESTALT`. SHA-256 is used by default.
+    pub fn payload_digest_algorithm(mut self, algorithm: DigestAlgorithm) -> Self {
+        self.payload_digest_algorithm = Some(algorithm);
+        self
+    }
+
+    /// Build the package for the given distribution or rpm version.
+    ///
+    /// Building fails with [`Error::UnsupportedByTarget`] if the package uses features the rpm
+    /// of the target doesn't support, such as zstd compression for EL7 or rich dependencies
+    /// before rpm 4.12. Header entries which the target's rpm doesn't know about, like the payload
+    /// digests, are left out.
+    ///
+    /// ```
+    /// # fn foo() -> Result<(), Box<dyn std::error::Error>> {
+    /// let pkg = rpm::PackageBuilder::new("foo", "1.0.0", "MIT", "x86_64", "some baz package")
+    ///     .target(rpm::Target::El7)
+    ///     .compression(rpm::CompressionType::Xz)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn target(mut self, target: Target) -> Self {
+        self.target = Some(target);
+        self
+    }
+
     /// Add an entry to the package changelog.
     ///
     /// The a changelog entry consists of an entry name (which includes author, email followed by
@@ -363,6 +829,128 @@ impl PackageBuilder {
         Ok(self)
     }
 
+    /// Add the spec file of a source package.
+    ///
+    /// The file is placed at the top level of the package, using its file name.
+    pub fn with_spec_file(mut self, source: impl AsRef<Path>) -> Result<Self, Error> {
+        let source = source.as_ref();
+        let spec = fs::read_to_string(source)?;
+        self.add_source_data(source, FileFlags::SPECFILE)?;
+        self.spec = Some(spec);
+        Ok(self)
+    }
+
+    /// Add a source file (`SourceN`) to a source package.
+    ///
+    /// Sources are numbered in the order they are added, starting with `Source0`. The file is
+    /// placed at the top level of the package, using its file name, so an error is returned if a
+    /// file with the same name was already added.
+    pub fn with_source_file(mut self, source: impl AsRef<Path>) -> Result<Self, Error> {
+        let name = self.add_source_data(source.as_ref(), FileFlags::empty())?;
+        self.sources.push(SourceEntry {
+            name,
+            included: true,
+        });
+        Ok(self)
+    }
+
+    /// Add a patch file (`PatchN`) to a source package.
+    ///
+    /// Patches are numbered in the order they are added, starting with `Patch0`. The file is
+    /// placed at the top level of the package, using its file name, so an error is returned if a
+    /// file with the same name was already added.
+    pub fn with_patch_file(mut self, source: impl AsRef<Path>) -> Result<Self, Error> {
+        let name = self.add_source_data(source.as_ref(), FileFlags::empty())?;
+        self.patches.push(SourceEntry {
+            name,
+            included: true,
+        });
+        Ok(self)
+    }
+
+    /// List a source of a source package, without including the file (`NoSource`).
+    ///
+    /// This is used for sources which may not be redistributed. It takes the next source
+    /// number, and makes the package a `nosrc` package.
+    pub fn no_source(mut self, name: impl Into<String>) -> Self {
+        self.sources.push(SourceEntry {
+            name: name.into(),
+            included: false,
+        });
+        self
+    }
+
+    /// List a patch of a source package, without including the file (`NoPatch`).
+    ///
+    /// It takes the next patch number, and makes the package a `nosrc` package.
+    pub fn no_patch(mut self, name: impl Into<String>) -> Self {
+        self.patches.push(SourceEntry {
+            name: name.into(),
+            included: false,
+        });
+        self
+    }
+
+    fn add_source_data(&mut self, source: &Path, flags: FileFlags) -> Result<String, Error> {
+        let name = source
+            .file_name()
+            .ok_or_else(|| Error::InvalidDestinationPath {
+                path: source.to_string_lossy().into_owned(),
+                desc: "no file name found",
+            })?
+            .to_string_lossy()
+            .into_owned();
+
+        let mut input = fs::File::open(source)?;
+        let mut content = Vec::new();
+        input.read_to_end(&mut content)?;
+        let mode = FileMode::from(file_mode(&input)? as i32);
+        let modified_at = input.metadata()?.modified()?.try_into()?;
+
+        self.add_source_content(name.clone(), content, mode, modified_at, flags)?;
+        Ok(name)
+    }
+
+    fn add_source_content(
+        &mut self,
+        name: String,
+        content: Vec<u8>,
+        mode: FileMode,
+        modified_at: Timestamp,
+        flags: FileFlags,
+    ) -> Result<(), Error> {
+        // all files of a source package are at the top level, so two sources with the same file
+        // name would overwrite each other
+        if self.files.contains_key(&name) {
+            return Err(Error::InvalidDestinationPath {
+                path: name,
+                desc: "a source file with the same name was already added",
+            });
+        }
+
+        let mut hasher = sha2::Sha256::default();
+        hasher.update(&content);
+        let entry = PackageFileEntry {
+            base_name: name.clone(),
+            size: content.len() as u64,
+            content,
+            flags,
+            user: "root".to_owned(),
+            group: "root".to_owned(),
+            mode,
+            link: String::new(),
+            modified_at,
+            // the files of source packages are not in any directory
+            dir: String::new(),
+            caps: None,
+            sha_checksum: hex::encode(hasher.finalize()),
+        };
+
+        self.directories.insert(String::new());
+        self.files.insert(name, entry);
+        Ok(())
+    }
+
     fn add_data(
         &mut self,
         content: Vec<u8>,
@@ -587,6 +1175,34 @@ impl PackageBuilder {
         self
     }
 
+    /// Add an ordering hint (`OrderWithRequires`)
+    ///
+    /// If a package matching the dependency is installed in the same transaction, it will be
+    /// installed before this package, as if it were a "requires". Unlike a "requires" however,
+    /// the dependency doesn't need to be satisfied.
+    pub fn order_with_requires(mut self, dep: Dependency) -> Self {
+        self.order_with_requires.push(dep);
+        self
+    }
+
+    /// Add a build dependency (`BuildRequires`) of a source package
+    ///
+    /// These are packages or capabilities which must be present in order to build the binary
+    /// packages. Like `rpmbuild`, they are stored as the "requires" of the source package.
+    pub fn build_requires(mut self, dep: Dependency) -> Self {
+        self.build_requires.push(dep);
+        self
+    }
+
+    /// Add a build conflict (`BuildConflicts`) of a source package
+    ///
+    /// These are packages which must not be present in order to build the binary packages. Like
+    /// `rpmbuild`, they are stored as the "conflicts" of the source package.
+    pub fn build_conflicts(mut self, dep: Dependency) -> Self {
+        self.build_conflicts.push(dep);
+        self
+    }
+
     /// Build the package
     pub fn build(self) -> Result<Package, Error> {
         let (lead, header_idx_tag, content) = self.prepare_data()?;
@@ -602,6 +1218,7 @@ impl PackageBuilder {
                 header_and_content_digest: header_and_content_digest_md5,
                 header_digest_sha1,
                 header_digest_sha256,
+                ..
             } = Package::create_sig_header_digests(header.as_slice(), content.as_slice())?;
 
             Header::<IndexSignatureTag>::builder()
@@ -643,6 +1260,7 @@ impl PackageBuilder {
             header_and_content_digest: header_and_content_digest_md5,
             header_digest_sha1,
             header_digest_sha256,
+            ..
         } = Package::create_sig_header_digests(header.as_slice(), content.as_slice())?;
 
         let now = Timestamp::now();
@@ -691,12 +1309,39 @@ impl PackageBuilder {
         // then the signature. Then we stitch all together.
         // Lead is not important. just build it here
 
-        let lead = Lead::new(&self.name);
+        let lead = Lead::new(&self.name, self.is_source);
+
+        if self.is_source {
+            if self.spec.is_none() {
+                return Err(Error::InvalidSourcePackage("no spec file was added"));
+            }
+            if self
+                .sources
+                .iter()
+                .chain(&self.patches)
+                .any(|s| !s.included)
+            {
+                self.arch = "nosrc".to_owned();
+            }
+            self.requires.append(&mut self.build_requires);
+            self.conflicts.append(&mut self.build_conflicts);
+        } else if self.spec.is_some() || !self.sources.is_empty() || !self.patches.is_empty() {
+            return Err(Error::InvalidSourcePackage(
+                "sources can only be added to packages created with `new_source`",
+            ));
+        }
+
+        let file_digest_algorithm = self
+            .file_digest_algorithm
+            .unwrap_or(DigestAlgorithm::Sha2_256);
+        let payload_digest_algorithm = self
+            .payload_digest_algorithm
+            .unwrap_or(DigestAlgorithm::Sha2_256);
 
-        // Calculate the sha256 of the archive as we write it into the compressor, so that we don't
+        // Calculate the digest of the archive as we write it into the compressor, so that we don't
         // need to keep two copies in memory simultaneously.
         let mut compressor: Compressor = self.compression.try_into()?;
-        let mut archive = Sha256Writer::new(&mut compressor);
+        let mut archive = DigestWriter::new(&mut compressor, payload_digest_algorithm);
 
         let mut ino_index = 1;
 
@@ -750,7 +1395,10 @@ impl PackageBuilder {
                 _ => entry.modified_at,
             };
             file_mtimes.push(mtime.into());
-            file_hashes.push(entry.sha_checksum.to_owned());
+            file_hashes.push(match file_digest_algorithm {
+                DigestAlgorithm::Sha2_256 => entry.sha_checksum.to_owned(),
+                algorithm => algorithm.hex_digest(&entry.content),
+            });
             file_linktos.push(entry.link.to_owned());
             file_flags.push(entry.flags.bits());
             file_usernames.push(entry.user.to_owned());
@@ -785,29 +1433,79 @@ impl PackageBuilder {
 
         self.provides
             .push(Dependency::eq(self.name.clone(), self.version.clone()));
-        self.provides.push(Dependency::eq(
-            format!("{}({})", self.name.clone(), self.arch.clone()),
-            self.version.clone(),
-        ));
+        if !self.is_source {
+            self.provides.push(Dependency::eq(
+                format!("{}({})", self.name.clone(), self.arch.clone()),
+                self.version.clone(),
+            ));
+        }
 
         self.requires
             .push(Dependency::rpmlib("CompressedFileNames", "3.0.4-1"));
 
-        self.requires
-            .push(Dependency::rpmlib("FileDigests", "4.6.0-1"));
-
-        self.requires
-            .push(Dependency::rpmlib("PayloadFilesHavePrefix", "4.0-1"));
+        // rpm understands md5 file digests without it
+        if file_digest_algorithm != DigestAlgorithm::Md5 {
+            self.requires
+                .push(Dependency::rpmlib("FileDigests", "4.6.0-1"));
+        }
 
-        if self.compression.compression_type() == CompressionType::Zstd {
+        // the files of source packages are not in any directory
+        if !self.is_source {
             self.requires
-                .push(Dependency::rpmlib("PayloadIsZstd", "5.4.18-1"));
+                .push(Dependency::rpmlib("PayloadFilesHavePrefix", "4.0-1"));
+        }
+
+        if let Some(requirement) = self.compression.compression_type().rpmlib_requirement() {
+            self.requires.push(requirement);
         }
 
         if uses_file_capabilities {
             self.requires
                 .push(Dependency::rpmlib("FileCaps", "4.6.1-1".to_owned()));
         }
+
+        // features used by the dependencies and versions, as rpmbuild detects them
+        let dependencies = || {
+            [
+                &self.provides,
+                &self.requires,
+                &self.conflicts,
+                &self.obsoletes,
+                &self.recommends,
+                &self.suggests,
+                &self.enhances,
+                &self.supplements,
+            ]
+            .into_iter()
+            .flatten()
+        };
+        let versions = || {
+            dependencies()
+                .map(|d| d.version.as_str())
+                .chain([self.version.as_str(), self.release.as_str()])
+        };
+        let uses_rich_dependencies = dependencies().any(|d| d.name.starts_with('('));
+        let uses_tilde = versions().any(|v| v.contains('~'));
+        let uses_caret = versions().any(|v| v.contains('^'));
+        if uses_rich_dependencies {
+            self.requires
+                .push(Dependency::rpmlib("RichDependencies", "4.12.0-1"));
+        }
+        if uses_tilde {
+            self.requires
+                .push(Dependency::rpmlib("TildeInVersions", "4.10.0-1"));
+        }
+        if uses_caret {
+            self.requires
+                .push(Dependency::rpmlib("CaretInVersions", "4.15.0-1"));
+        }
+
+        if let Some(target) = self.target {
+            target.check_rpmlib_requirements(&self.requires)?;
+            if self.pre_untrans_script.is_some() || self.post_untrans_script.is_some() {
+                target.require("%preuntrans and %postuntrans", RpmVersion::new(4, 18, 0))?;
+            }
+        }
         // TODO: as per https://rpm-software-management.github.io/rpm/manual/users_and_groups.html,
         // at some point in the future this might make sense as hard requirements, but since it's a new feature,
         // they have to be weak requirements to avoid breaking things.
@@ -899,17 +1597,35 @@ impl PackageBuilder {
             supplements_versions.push(d.version);
         }
 
+        let mut order_names = Vec::new();
+        let mut order_flags = Vec::new();
+        let mut order_versions = Vec::new();
+
+        for d in self.order_with_requires.into_iter() {
+            order_names.push(d.name);
+            order_flags.push(d.flags.bits());
+            order_versions.push(d.version);
+        }
+
         let offset = 0;
         let small_package = combined_file_sizes <= u32::MAX.into();
 
         let mut actual_records = vec![
             // Existence of this tag is how rpm decides whether or not a package is a source rpm or binary rpm
             // If the SOURCERPM tag is set, then the package is seen as a binary rpm.
-            IndexEntry::new(
-                IndexTag::RPMTAG_SOURCERPM,
-                offset,
-                IndexData::StringTag("(none)".to_string()),
-            ),
+            if self.is_source {
+                IndexEntry::new(
+                    IndexTag::RPMTAG_SOURCEPACKAGE,
+                    offset,
+                    IndexData::Int32(vec![1]),
+                )
+            } else {
+                IndexEntry::new(
+                    IndexTag::RPMTAG_SOURCERPM,
+                    offset,
+                    IndexData::StringTag(self.source_rpm.unwrap_or_else(|| "(none)".to_string())),
+                )
+            },
             IndexEntry::new(
                 IndexTag::RPMTAG_HEADERI18NTABLE,
                 offset,
@@ -920,17 +1636,11 @@ impl PackageBuilder {
                 offset,
                 IndexData::StringTag(self.name),
             ),
-            IndexEntry::new(
-                IndexTag::RPMTAG_EPOCH,
-                offset,
-                IndexData::Int32(vec![self.epoch]),
-            ),
             IndexEntry::new(
                 IndexTag::RPMTAG_RPMVERSION,
                 offset,
                 IndexData::StringTag(format!("rpm-rs {}", env!("CARGO_PKG_VERSION"))),
             ),
-            // @todo: write RPMTAG_PLATFORM?
             IndexEntry::new(
                 IndexTag::RPMTAG_VERSION,
                 offset,
@@ -1001,6 +1711,14 @@ impl PackageBuilder {
             ),
         ];
 
+        if let Some(epoch) = self.epoch {
+            actual_records.push(IndexEntry::new(
+                IndexTag::RPMTAG_EPOCH,
+                offset,
+                IndexData::Int32(vec![epoch]),
+            ));
+        }
+
         let now = Timestamp::now();
         let build_time = match self.source_date {
             Some(t) if t < now => t,
@@ -1108,7 +1826,7 @@ impl PackageBuilder {
                 IndexEntry::new(
                     IndexTag::RPMTAG_FILEDIGESTALGO,
                     offset,
-                    IndexData::Int32(vec![DigestAlgorithm::Sha2_256 as u32]),
+                    IndexData::Int32(vec![file_digest_algorithm as u32]),
                 ),
                 IndexEntry::new(
                     IndexTag::RPMTAG_FILEVERIFYFLAGS,
@@ -1162,47 +1880,42 @@ impl PackageBuilder {
         ]);
 
         // digest of the uncompressed raw archive calculated on the inner writer
-        let raw_archive_digest_sha256 = hex::encode(archive.into_digest());
+        let raw_archive_digest = archive.into_hex_digest();
         let payload = compressor.finish_compression()?;
 
         // digest of the post-compression archive (payload)
-        let payload_digest_sha256 = {
-            let mut hasher = sha2::Sha256::default();
-            hasher.update(payload.as_slice());
-            hex::encode(hasher.finalize())
-        };
+        let payload_digest = payload_digest_algorithm.hex_digest(&payload);
 
-        actual_records.extend([
-            IndexEntry::new(
-                IndexTag::RPMTAG_PAYLOADDIGEST,
-                offset,
-                IndexData::StringArray(vec![payload_digest_sha256]),
-            ),
-            IndexEntry::new(
-                IndexTag::RPMTAG_PAYLOADDIGESTALGO,
-                offset,
-                IndexData::Int32(vec![DigestAlgorithm::Sha2_256 as u32]),
-            ),
-            IndexEntry::new(
+        // rpm knows about the payload digest since 4.14, and the one of the uncompressed archive
+        // since 4.16
+        let target_supports = |version| self.target.map_or(true, |t| t.supports(version));
+        if target_supports(RpmVersion::new(4, 14, 0)) {
+            actual_records.extend([
+                IndexEntry::new(
+                    IndexTag::RPMTAG_PAYLOADDIGEST,
+                    offset,
+                    IndexData::StringArray(vec![payload_digest]),
+                ),
+                IndexEntry::new(
+                    IndexTag::RPMTAG_PAYLOADDIGESTALGO,
+                    offset,
+                    IndexData::Int32(vec![payload_digest_algorithm as u32]),
+                ),
+            ]);
+        }
+        if target_supports(RpmVersion::new(4, 16, 0)) {
+            actual_records.push(IndexEntry::new(
                 IndexTag::RPMTAG_PAYLOADDIGESTALT,
                 offset,
-                IndexData::StringArray(vec![raw_archive_digest_sha256]),
-            ),
-        ]);
-
-        let compression_details = match self.compression {
-            CompressionWithLevel::None => None,
-            CompressionWithLevel::Gzip(level) => Some(("gzip".to_owned(), level.to_string())),
-            CompressionWithLevel::Zstd(level) => Some(("zstd".to_owned(), level.to_string())),
-            CompressionWithLevel::Xz(level) => Some(("xz".to_owned(), level.to_string())),
-            CompressionWithLevel::Bzip2(level) => Some(("bzip2".to_owned(), level.to_string())),
-        };
+                IndexData::StringArray(vec![raw_archive_digest]),
+            ));
+        }
 
-        if let Some((compression_name, compression_level)) = compression_details {
+        if let Some((compression_name, compression_level)) = self.compression.payload_tags() {
             actual_records.push(IndexEntry::new(
                 IndexTag::RPMTAG_PAYLOADCOMPRESSOR,
                 offset,
-                IndexData::StringTag(compression_name),
+                IndexData::StringTag(compression_name.to_owned()),
             ));
             actual_records.push(IndexEntry::new(
                 IndexTag::RPMTAG_PAYLOADFLAGS,
@@ -1355,6 +2068,24 @@ impl PackageBuilder {
             ));
         }
 
+        if !order_flags.is_empty() {
+            actual_records.push(IndexEntry::new(
+                IndexTag::RPMTAG_ORDERNAME,
+                offset,
+                IndexData::StringArray(order_names),
+            ));
+            actual_records.push(IndexEntry::new(
+                IndexTag::RPMTAG_ORDERVERSION,
+                offset,
+                IndexData::StringArray(order_versions),
+            ));
+            actual_records.push(IndexEntry::new(
+                IndexTag::RPMTAG_ORDERFLAGS,
+                offset,
+                IndexData::Int32(order_flags),
+            ));
+        }
+
         if let Some(script) = self.pre_inst_script {
             script.apply(&mut actual_records, offset, PREIN_TAGS);
         }
@@ -1387,6 +2118,52 @@ impl PackageBuilder {
             script.apply(&mut actual_records, offset, POSTUNTRANS_TAGS);
         }
 
+        if let Some(script) = self.verify_script {
+            script.apply(&mut actual_records, offset, VERIFYSCRIPT_TAGS);
+        }
+
+        if let Some(spec) = self.spec {
+            actual_records.push(IndexEntry::new(
+                IndexTag::RPMTAG_SPEC,
+                offset,
+                IndexData::StringTag(spec),
+            ));
+        }
+
+        for (sources, names_tag, excluded_tag) in [
+            (
+                self.sources,
+                IndexTag::RPMTAG_SOURCE,
+                IndexTag::RPMTAG_NOSOURCE,
+            ),
+            (
+                self.patches,
+                IndexTag::RPMTAG_PATCH,
+                IndexTag::RPMTAG_NOPATCH,
+            ),
+        ] {
+            if sources.is_empty() {
+                continue;
+            }
+            let excluded: Vec<u32> = (0u32..)
+                .zip(&sources)
+                .filter(|(_, source)| !source.included)
+                .map(|(number, _)| number)
+                .collect();
+            actual_records.push(IndexEntry::new(
+                names_tag,
+                offset,
+                IndexData::StringArray(sources.into_iter().map(|s| s.name).collect()),
+            ));
+            if !excluded.is_empty() {
+                actual_records.push(IndexEntry::new(
+                    excluded_tag,
+                    offset,
+                    IndexData::Int32(excluded),
+                ));
+            }
+        }
+
         if let Some(vendor) = self.vendor {
             actual_records.push(IndexEntry::new(
                 IndexTag::RPMTAG_VENDOR,
@@ -1419,6 +2196,21 @@ impl PackageBuilder {
             ));
         }
 
+        for (tag, value) in [
+            (IndexTag::RPMTAG_DISTURL, self.dist_url),
+            (IndexTag::RPMTAG_DISTRIBUTION, self.distribution),
+            (IndexTag::RPMTAG_DISTTAG, self.disttag),
+            (IndexTag::RPMTAG_BUGURL, self.bug_url),
+            (IndexTag::RPMTAG_PLATFORM, self.platform),
+            (IndexTag::RPMTAG_OPTFLAGS, self.optflags),
+            (IndexTag::RPMTAG_MODULARITYLABEL, self.modularity_label),
+            (IndexTag::RPMTAG_SOURCELICENSE, self.source_license),
+        ] {
+            if let Some(value) = value {
+                actual_records.push(IndexEntry::new(tag, offset, IndexData::StringTag(value)));
+            }
+        }
+
         let header = Header::from_entries(actual_records, IndexTag::RPMTAG_HEADERIMMUTABLE);
 
         Ok((lead, header, payload))
@@ -1,8 +1,12 @@
-use std::io::Write;
+use std::io::{self, Write};
 
-use crate::errors::*;
+use crate::{errors::*, Dependency};
 
 /// Supported payload compression types.
+///
+/// These are the codecs rpm can read payloads in. lz4 is deliberately not supported: rpm can't
+/// read lz4 compressed payloads and dnf can't read lz4 compressed repository metadata, so there
+/// is nothing to read, and packages written with it would not install.
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
 pub enum CompressionType {
     #[default]
@@ -11,6 +15,43 @@ pub enum CompressionType {
     Zstd,
     Xz,
     Bzip2,
+    /// The legacy lzma ("alone") format, used by older SUSE and RHEL packages
+    Lzma,
+}
+
+impl CompressionType {
+    /// Detect the compression type from the magic bytes at the start of a payload. Returns `None`
+    /// if the data doesn't look like any supported format.
+    ///
+    /// This is useful if `RPMTAG_PAYLOADCOMPRESSOR` is missing or doesn't match the payload.
+    pub fn from_magic(data: &[u8]) -> Option<CompressionType> {
+        const MAGIC: &[(&[u8], CompressionType)] = &[
+            (b"070701", CompressionType::None),
+            (b"070702", CompressionType::None),
+            (&[0x1f, 0x8b], CompressionType::Gzip),
+            (&[0x28, 0xb5, 0x2f, 0xfd], CompressionType::Zstd),
+            (&[0xfd, b'7', b'z', b'X', b'Z', 0x00], CompressionType::Xz),
+            (b"BZh", CompressionType::Bzip2),
+            // lzma alone has no magic, but the properties byte virtually always is 0x5d and the
+            // dictionary size is a multiple of 64KiB
+            (&[0x5d, 0x00, 0x00], CompressionType::Lzma),
+        ];
+        MAGIC
+            .iter()
+            .find(|(magic, _)| data.starts_with(magic))
+            .map(|(_, compression)| *compression)
+    }
+
+    /// The `rpmlib()` feature which rpm requires to read payloads of this type, if any.
+    pub(crate) fn rpmlib_requirement(&self) -> Option<Dependency> {
+        match self {
+            CompressionType::None | CompressionType::Gzip => None,
+            CompressionType::Zstd => Some(Dependency::rpmlib("PayloadIsZstd", "5.4.18-1")),
+            CompressionType::Xz => Some(Dependency::rpmlib("PayloadIsXz", "5.2-1")),
+            CompressionType::Bzip2 => Some(Dependency::rpmlib("PayloadIsBzip2", "3.0.5-1")),
+            CompressionType::Lzma => Some(Dependency::rpmlib("PayloadIsLzma", "4.4.6-1")),
+        }
+    }
 }
 
 impl std::str::FromStr for CompressionType {
@@ -21,6 +62,7 @@ impl std::str::FromStr for CompressionType {
             "zstd" => Ok(CompressionType::Zstd),
             "xz" => Ok(CompressionType::Xz),
             "bzip2" => Ok(CompressionType::Bzip2),
+            "lzma" => Ok(CompressionType::Lzma),
             _ => Err(Error::UnknownCompressorType(raw.to_string())),
         }
     }
@@ -29,6 +71,11 @@ impl std::str::FromStr for CompressionType {
 pub enum Compressor {
     None(Vec<u8>),
     Gzip(flate2::write::GzEncoder<Vec<u8>>),
+    ParallelGzip {
+        data: Vec<u8>,
+        level: flate2::Compression,
+        threads: usize,
+    },
     Zstd(zstd::stream::Encoder<'static, Vec<u8>>),
     Xz(xz2::write::XzEncoder<Vec<u8>>),
     Bzip2(bzip2::write::BzEncoder<Vec<u8>>),
@@ -38,31 +85,121 @@ impl TryFrom<CompressionWithLevel> for Compressor {
     type Error = Error;
 
     fn try_from(value: CompressionWithLevel) -> Result<Self, Self::Error> {
-        match value {
-            CompressionWithLevel::None => Ok(Compressor::None(Vec::new())),
-            CompressionWithLevel::Gzip(level) => Ok(Compressor::Gzip(
+        CompressionOptions::from(value).try_into()
+    }
+}
+
+impl TryFrom<CompressionOptions> for Compressor {
+    type Error = Error;
+
+    fn try_from(value: CompressionOptions) -> Result<Self, Self::Error> {
+        let threads = value.threads.map(worker_count);
+        match (value.compression, threads) {
+            (CompressionWithLevel::None, _) => Ok(Compressor::None(Vec::new())),
+            (CompressionWithLevel::Gzip(level), Some(threads)) if threads > 1 => {
+                Ok(Compressor::ParallelGzip {
+                    data: Vec::new(),
+                    level: flate2::Compression::new(level),
+                    threads: threads as usize,
+                })
+            }
+            (CompressionWithLevel::Gzip(level), _) => Ok(Compressor::Gzip(
                 flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::new(level)),
             )),
-            CompressionWithLevel::Zstd(level) => Ok(Compressor::Zstd(zstd::stream::Encoder::new(
-                Vec::new(),
-                level,
-            )?)),
-            CompressionWithLevel::Xz(level) => Ok(Compressor::Xz(xz2::write::XzEncoder::new(
-                Vec::new(),
-                level,
-            ))),
-            CompressionWithLevel::Bzip2(level) => Ok(Compressor::Bzip2(
+            (CompressionWithLevel::Zstd(level), threads) => {
+                let mut encoder = zstd::stream::Encoder::new(Vec::new(), level)?;
+                if let Some(threads) = threads {
+                    encoder.multithread(threads)?;
+                }
+                if value.long_distance_matching {
+                    encoder.long_distance_matching(true)?;
+                }
+                if let Some(window_log) = value.window_log {
+                    encoder.window_log(window_log)?;
+                }
+                Ok(Compressor::Zstd(encoder))
+            }
+            (CompressionWithLevel::Xz(level), Some(threads)) => {
+                let stream = xz2::stream::MtStreamBuilder::new()
+                    .threads(threads)
+                    .preset(level)
+                    .check(xz2::stream::Check::Crc64)
+                    .encoder()
+                    .map_err(io::Error::from)?;
+                Ok(Compressor::Xz(xz2::write::XzEncoder::new_stream(
+                    Vec::new(),
+                    stream,
+                )))
+            }
+            (CompressionWithLevel::Xz(level), None) => Ok(Compressor::Xz(
+                xz2::write::XzEncoder::new(Vec::new(), level),
+            )),
+            (CompressionWithLevel::Lzma(level), _) => {
+                let options =
+                    xz2::stream::LzmaOptions::new_preset(level).map_err(io::Error::from)?;
+                let stream =
+                    xz2::stream::Stream::new_lzma_encoder(&options).map_err(io::Error::from)?;
+                Ok(Compressor::Xz(xz2::write::XzEncoder::new_stream(
+                    Vec::new(),
+                    stream,
+                )))
+            }
+            (CompressionWithLevel::Bzip2(level), _) => Ok(Compressor::Bzip2(
                 bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::new(level)),
             )),
         }
     }
 }
 
+/// Resolve a requested worker count, where 0 means one worker per available CPU.
+fn worker_count(threads: u32) -> u32 {
+    if threads > 0 {
+        return threads;
+    }
+    std::thread::available_parallelism()
+        .map(|n| n.get() as u32)
+        .unwrap_or(1)
+}
+
+/// The smallest amount of input handed to a single gzip worker.
+const GZIP_MIN_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Compress the data pigz-style: split it into chunks which are compressed concurrently, each
+/// into a separate gzip member. The concatenated members form a valid gzip stream.
+fn parallel_gzip(
+    data: &[u8],
+    level: flate2::Compression,
+    threads: usize,
+) -> Result<Vec<u8>, io::Error> {
+    let chunk_size = std::cmp::max(GZIP_MIN_CHUNK_SIZE, (data.len() + threads - 1) / threads);
+    let compress = |chunk: &[u8]| {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), level);
+        encoder.write_all(chunk)?;
+        encoder.finish()
+    };
+    if data.len() <= chunk_size {
+        return compress(data);
+    }
+
+    let members = std::thread::scope(|scope| {
+        let workers: Vec<_> = data
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || compress(chunk)))
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("gzip worker thread panicked"))
+            .collect::<Result<Vec<_>, _>>()
+    })?;
+    Ok(members.concat())
+}
+
 impl Write for Compressor {
     fn write(&mut self, content: &[u8]) -> Result<usize, std::io::Error> {
         match self {
             Compressor::None(data) => data.write(content),
             Compressor::Gzip(encoder) => encoder.write(content),
+            Compressor::ParallelGzip { data, .. } => data.write(content),
             Compressor::Zstd(encoder) => encoder.write(content),
             Compressor::Xz(encoder) => encoder.write(content),
             Compressor::Bzip2(encoder) => encoder.write(content),
@@ -72,6 +209,7 @@ impl Write for Compressor {
         match self {
             Compressor::None(data) => data.flush(),
             Compressor::Gzip(encoder) => encoder.flush(),
+            Compressor::ParallelGzip { data, .. } => data.flush(),
             Compressor::Zstd(encoder) => encoder.flush(),
             Compressor::Xz(encoder) => encoder.flush(),
             Compressor::Bzip2(encoder) => encoder.flush(),
@@ -84,6 +222,11 @@ impl Compressor {
         match self {
             Compressor::None(data) => Ok(data),
             Compressor::Gzip(encoder) => Ok(encoder.finish()?),
+            Compressor::ParallelGzip {
+                data,
+                level,
+                threads,
+            } => Ok(parallel_gzip(&data, level, threads)?),
             Compressor::Zstd(encoder) => Ok(encoder.finish()?),
             Compressor::Xz(encoder) => Ok(encoder.finish()?),
             Compressor::Bzip2(encoder) => Ok(encoder.finish()?),
@@ -91,6 +234,36 @@ impl Compressor {
     }
 }
 
+/// Wrap a reader of a compressed payload with the matching decoder.
+pub(crate) fn decompress_stream<'a>(
+    compression: CompressionType,
+    reader: impl io::BufRead + 'a,
+) -> Result<Box<dyn io::Read + 'a>, Error> {
+    Ok(match compression {
+        CompressionType::None => Box::new(reader),
+        CompressionType::Gzip => Box::new(flate2::bufread::MultiGzDecoder::new(reader)),
+        CompressionType::Zstd => {
+            let mut decoder = zstd::stream::read::Decoder::with_buffer(reader)?;
+            // payloads compressed in long mode may use windows beyond the default limit
+            decoder.window_log_max(ZSTD_WINDOW_LOG_MAX)?;
+            Box::new(decoder)
+        }
+        CompressionType::Xz => Box::new(xz2::bufread::XzDecoder::new_multi_decoder(reader)),
+        CompressionType::Bzip2 => Box::new(bzip2::bufread::MultiBzDecoder::new(reader)),
+        CompressionType::Lzma => Box::new(xz2::bufread::XzDecoder::new_stream(
+            reader,
+            xz2::stream::Stream::new_lzma_decoder(u64::MAX).map_err(io::Error::from)?,
+        )),
+    })
+}
+
+/// The largest zstd window accepted when decompressing, matching the largest window zstd supports
+/// on the platform (`ZSTD_WINDOWLOG_MAX`), as setting a larger one fails.
+#[cfg(target_pointer_width = "64")]
+const ZSTD_WINDOW_LOG_MAX: u32 = 31;
+#[cfg(not(target_pointer_width = "64"))]
+const ZSTD_WINDOW_LOG_MAX: u32 = 30;
+
 /// Supported compression types, with an associated compression level. This is used for setting
 /// a custom compression configuration during RPM building.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -100,9 +273,20 @@ pub enum CompressionWithLevel {
     Gzip(u32),
     Xz(u32),
     Bzip2(u32),
+    Lzma(u32),
 }
 
 impl CompressionWithLevel {
+    /// Compress with the given number of worker threads. Passing 0 uses one worker per
+    /// available CPU.
+    ///
+    /// ```
+    /// let compression = rpm::CompressionWithLevel::Zstd(19).threads(8);
+    /// ```
+    pub fn threads(self, threads: u32) -> CompressionOptions {
+        CompressionOptions::from(self).threads(threads)
+    }
+
     pub(crate) fn compression_type(&self) -> CompressionType {
         match self {
             Self::None => CompressionType::None,
@@ -110,6 +294,7 @@ impl CompressionWithLevel {
             Self::Zstd(_) => CompressionType::Zstd,
             Self::Xz(_) => CompressionType::Xz,
             Self::Bzip2(_) => CompressionType::Bzip2,
+            Self::Lzma(_) => CompressionType::Lzma,
         }
     }
 }
@@ -128,6 +313,271 @@ impl From<CompressionType> for CompressionWithLevel {
             CompressionType::Xz => CompressionWithLevel::Xz(9),
             CompressionType::Zstd => CompressionWithLevel::Zstd(19),
             CompressionType::Bzip2 => CompressionWithLevel::Bzip2(9),
+            CompressionType::Lzma => CompressionWithLevel::Lzma(9),
+        }
+    }
+}
+
+/// Payload compression settings: the compression type and level, along with options which
+/// only affect how fast the payload is compressed.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CompressionOptions {
+    /// Compression type and level
+    pub compression: CompressionWithLevel,
+    /// Number of worker threads, where 0 means one per available CPU. `None` compresses on the
+    /// calling thread.
+    pub threads: Option<u32>,
+    /// Whether zstd long distance matching is enabled
+    pub long_distance_matching: bool,
+    /// The zstd window size as a power of two
+    pub window_log: Option<u32>,
+}
+
+impl CompressionOptions {
+    /// Sets the number of worker threads, where 0 means one per available CPU.
+    ///
+    /// Zstd and xz use the multi-threaded mode of the respective library. Gzip payloads are
+    /// compressed in chunks concurrently, like pigz does. Bzip2 compression is always
+    /// single-threaded.
+    pub fn threads(mut self, threads: u32) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// Enables zstd long distance matching, which improves the compression of large payloads
+    /// containing repetitions far apart. Ignored for other compression types.
+    pub fn long_distance_matching(mut self) -> Self {
+        self.long_distance_matching = true;
+        self
+    }
+
+    /// Sets the zstd window size to `2^window_log` bytes, in the range 10 to 31 (30 on 32-bit
+    /// platforms), and enables long distance matching. Ignored for other compression types.
+    ///
+    /// Decompressing payloads with windows larger than 128MiB (a window log of 27) requires as
+    /// much memory, and rpm needs to be told to allow this.
+    pub fn window_log(mut self, window_log: u32) -> Self {
+        self.long_distance_matching = true;
+        self.window_log = Some(window_log);
+        self
+    }
+
+    /// The values of `RPMTAG_PAYLOADCOMPRESSOR` and `RPMTAG_PAYLOADFLAGS`, if the payload is
+    /// compressed. The thread count and zstd long mode are recorded the same way rpm does, e.g.
+    /// `19T8` or `19L30`.
+    pub(crate) fn payload_tags(&self) -> Option<(&'static str, String)> {
+        let (name, level) = match self.compression {
+            CompressionWithLevel::None => return None,
+            CompressionWithLevel::Gzip(level) => ("gzip", level.to_string()),
+            CompressionWithLevel::Zstd(level) => ("zstd", level.to_string()),
+            CompressionWithLevel::Xz(level) => ("xz", level.to_string()),
+            CompressionWithLevel::Bzip2(level) => return Some(("bzip2", level.to_string())),
+            CompressionWithLevel::Lzma(level) => return Some(("lzma", level.to_string())),
+        };
+        let mut flags = level;
+        if let Some(threads) = self.threads {
+            flags.push_str(&format!("T{threads}"));
+        }
+        if name == "zstd" && self.long_distance_matching {
+            flags.push('L');
+            if let Some(window_log) = self.window_log {
+                flags.push_str(&window_log.to_string());
+            }
+        }
+        Some((name, flags))
+    }
+
+    pub(crate) fn compression_type(&self) -> CompressionType {
+        self.compression.compression_type()
+    }
+}
+
+impl CompressionOptions {
+    /// Parse the compression settings from `RPMTAG_PAYLOADFLAGS`, which holds the compression
+    /// level, optionally followed by `T<threads>` and zstd's `L<window log>`. Settings missing
+    /// from the flags take their default values.
+    ///
+    /// The flags are an rpmio mode string, so other option letters, like gzip's `h` (huffman
+    /// only) strategy in `9h`, are valid too. They don't affect the settings tracked here and
+    /// are ignored.
+    pub(crate) fn from_payload_flags(
+        compression: CompressionType,
+        flags: &str,
+    ) -> Result<Self, Error> {
+        let invalid = || Error::InvalidPayloadFlags(flags.to_owned());
+        // split off the number following the start of the string
+        fn number(s: &str) -> (Option<&str>, &str) {
+            let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+            match end {
+                0 => (None, s),
+                _ => (Some(&s[..end]), &s[end..]),
+            }
+        }
+
+        let mut options = CompressionOptions::from(compression);
+        let (level, mut rest) = number(flags);
+        if let Some(level) = level {
+            let level: u32 = level.parse().map_err(|_| invalid())?;
+            options.compression = match compression {
+                CompressionType::None => CompressionWithLevel::None,
+                CompressionType::Gzip => CompressionWithLevel::Gzip(level),
+                CompressionType::Zstd => {
+                    CompressionWithLevel::Zstd(level.try_into().map_err(|_| invalid())?)
+                }
+                CompressionType::Xz => CompressionWithLevel::Xz(level),
+                CompressionType::Bzip2 => CompressionWithLevel::Bzip2(level),
+                CompressionType::Lzma => CompressionWithLevel::Lzma(level),
+            };
+        }
+        while let Some(option) = rest.chars().next() {
+            let (value, remaining) = number(&rest[option.len_utf8()..]);
+            let value = value
+                .map(|value| value.parse::<u32>().map_err(|_| invalid()))
+                .transpose()?;
+            match option {
+                'T' => options.threads = Some(value.unwrap_or(0)),
+                'L' => {
+                    options.long_distance_matching = true;
+                    options.window_log = value;
+                }
+                _ => {}
+            }
+            rest = remaining;
+        }
+        Ok(options)
+    }
+}
+
+impl From<CompressionWithLevel> for CompressionOptions {
+    fn from(compression: CompressionWithLevel) -> Self {
+        CompressionOptions {
+            compression,
+            threads: None,
+            long_distance_matching: false,
+            window_log: None,
+        }
+    }
+}
+
+impl From<CompressionType> for CompressionOptions {
+    fn from(value: CompressionType) -> Self {
+        CompressionWithLevel::from(value).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_payload_flags() {
+        for (compression, flags, expected) in [
+            (
+                CompressionType::Gzip,
+                "",
+                CompressionWithLevel::Gzip(9).into(),
+            ),
+            (CompressionType::Xz, "2", CompressionWithLevel::Xz(2).into()),
+            (
+                CompressionType::Bzip2,
+                "9",
+                CompressionWithLevel::Bzip2(9).into(),
+            ),
+            (
+                CompressionType::Lzma,
+                "7",
+                CompressionWithLevel::Lzma(7).into(),
+            ),
+            (
+                CompressionType::Xz,
+                "7T16",
+                CompressionWithLevel::Xz(7).threads(16),
+            ),
+            (
+                CompressionType::Zstd,
+                "19T",
+                CompressionWithLevel::Zstd(19).threads(0),
+            ),
+            (
+                CompressionType::Zstd,
+                "19T8L",
+                CompressionWithLevel::Zstd(19)
+                    .threads(8)
+                    .long_distance_matching(),
+            ),
+            (
+                CompressionType::Zstd,
+                "3L30",
+                CompressionOptions::from(CompressionWithLevel::Zstd(3)).window_log(30),
+            ),
+        ] {
+            let options = CompressionOptions::from_payload_flags(compression, flags).unwrap();
+            assert_eq!(options, expected);
+            if !flags.is_empty() && flags != "19T" {
+                assert_eq!(options.payload_tags().unwrap().1, flags);
+            }
         }
+
+        // other rpmio mode letters are ignored
+        for (compression, flags, expected) in [
+            (
+                CompressionType::Gzip,
+                "9h",
+                CompressionWithLevel::Gzip(9).into(),
+            ),
+            (
+                CompressionType::Gzip,
+                "6fR",
+                CompressionWithLevel::Gzip(6).into(),
+            ),
+            (
+                CompressionType::Zstd,
+                "19T8x",
+                CompressionWithLevel::Zstd(19).threads(8),
+            ),
+        ] {
+            assert_eq!(
+                CompressionOptions::from_payload_flags(compression, flags).unwrap(),
+                expected
+            );
+        }
+
+        for flags in ["99999999999", "9T99999999999"] {
+            assert!(matches!(
+                CompressionOptions::from_payload_flags(CompressionType::Zstd, flags),
+                Err(Error::InvalidPayloadFlags(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_parallel_gzip() {
+        let data: Vec<u8> = (0..8 * GZIP_MIN_CHUNK_SIZE as u32)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let compressed = parallel_gzip(&data, flate2::Compression::fast(), 4).unwrap();
+
+        // one gzip member per chunk, the first member alone only holds part of the data
+        let mut first_member = Vec::new();
+        flate2::read::GzDecoder::new(compressed.as_slice())
+            .read_to_end(&mut first_member)
+            .unwrap();
+        assert_eq!(first_member.len(), 2 * GZIP_MIN_CHUNK_SIZE);
+
+        let mut decompressed = Vec::new();
+        decompress_stream(CompressionType::Gzip, compressed.as_slice())
+            .unwrap()
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, data);
+
+        let compressed = parallel_gzip(&[], flate2::Compression::fast(), 4).unwrap();
+        let mut decompressed = Vec::new();
+        decompress_stream(CompressionType::Gzip, compressed.as_slice())
+            .unwrap()
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert!(decompressed.is_empty());
     }
 }
@@ -0,0 +1,229 @@
+//! Detection of files which would conflict when installing a set of packages together.
+use std::{collections::BTreeMap, fmt::Display, path::PathBuf};
+
+use crate::{errors::*, FileEntry, FileFlags, FileMode, IndexTag, Nevra, PackageMetadata};
+
+/// What differs between two files installed to the same path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum FileConflictKind {
+    /// One is e.g. a directory, the other a regular file
+    FileType,
+    /// The permissions differ
+    Mode,
+    /// The owning user or group differs
+    Owner,
+    /// Both are symbolic links, but to different targets
+    LinkTarget,
+    /// Both are regular files, but with different contents
+    Digest,
+}
+
+impl Display for FileConflictKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            FileConflictKind::FileType => "file type",
+            FileConflictKind::Mode => "mode",
+            FileConflictKind::Owner => "owner",
+            FileConflictKind::LinkTarget => "symlink target",
+            FileConflictKind::Digest => "contents",
+        })
+    }
+}
+
+/// A path which is installed by two packages with different content or metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileConflict {
+    /// The conflicting path
+    pub path: PathBuf,
+    /// The package which comes first in the given set
+    pub package: Nevra,
+    /// The package that conflicts with it
+    pub other_package: Nevra,
+    /// The first difference found between both files
+    pub kind: FileConflictKind,
+}
+
+impl Display for FileConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "file {} conflicts between attempted installs of {} and {} ({} differs)",
+            self.path.display(),
+            self.package,
+            self.other_package,
+            self.kind
+        )
+    }
+}
+
+/// Compare two files installed to the same path, following rpm's rules.
+fn compare_files(a: &FileEntry, b: &FileEntry) -> Option<FileConflictKind> {
+    if a.flags.contains(FileFlags::GHOST) || b.flags.contains(FileFlags::GHOST) {
+        return None;
+    }
+
+    let both_links = matches!(
+        (a.mode, b.mode),
+        (FileMode::SymbolicLink { .. }, FileMode::SymbolicLink { .. })
+    );
+    if a.mode.file_type() != b.mode.file_type() {
+        return Some(FileConflictKind::FileType);
+    }
+    if !both_links && a.mode.permissions() != b.mode.permissions() {
+        return Some(FileConflictKind::Mode);
+    }
+
+    match a.mode {
+        FileMode::SymbolicLink { .. } | FileMode::Regular { .. } if a.ownership != b.ownership => {
+            Some(FileConflictKind::Owner)
+        }
+        FileMode::SymbolicLink { .. } if a.linkto != b.linkto => Some(FileConflictKind::LinkTarget),
+        FileMode::Regular { .. } if a.size != b.size || a.digest != b.digest => {
+            Some(FileConflictKind::Digest)
+        }
+        _ => None,
+    }
+}
+
+/// Find all files which are installed by more than one of the given packages, and which would
+/// conflict with each other.
+///
+/// The rules are the ones rpm applies to packages installed in the same transaction: a path may
+/// be shared as long as both packages install exactly the same thing there, i.e. the file type,
+/// mode, owner and content (digest or symlink target) are all identical. Only the file type and
+/// mode matter for directories.
+///
+/// `%ghost` files never conflict, as they aren't actually installed by either package. `%config`
+/// files get no special treatment here, as for rpm it only matters when they have been modified
+/// on disk. Files with different non-zero colors (e.g. 32-bit and 64-bit ELF binaries from
+/// multilib packages) don't conflict either, rpm installs the one matching the preferred color
+/// of the system.
+pub fn check_file_conflicts<'a>(
+    packages: impl IntoIterator<Item = &'a PackageMetadata>,
+) -> Result<Vec<FileConflict>, Error> {
+    // path -> (package, file, color)
+    let mut paths: BTreeMap<PathBuf, Vec<(usize, FileEntry, u32)>> = BTreeMap::new();
+    let mut nevras = Vec::new();
+    for (idx, package) in packages.into_iter().enumerate() {
+        nevras.push(package.get_nevra()?);
+        let colors = match package
+            .header
+            .get_entry_data_as_u32_array(IndexTag::RPMTAG_FILECOLORS)
+        {
+            Ok(colors) => colors,
+            Err(Error::TagNotFound(_)) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        for (file_idx, entry) in package.get_file_entries()?.into_iter().enumerate() {
+            let color = colors.get(file_idx).copied().unwrap_or(0);
+            paths
+                .entry(entry.path.clone())
+                .or_default()
+                .push((idx, entry, color));
+        }
+    }
+
+    let mut conflicts = Vec::new();
+    for (path, entries) in paths {
+        for (i, (pkg_a, file_a, color_a)) in entries.iter().enumerate() {
+            for (pkg_b, file_b, color_b) in &entries[i + 1..] {
+                if pkg_a == pkg_b || (color_a & color_b == 0 && *color_a != 0 && *color_b != 0) {
+                    continue;
+                }
+                if let Some(kind) = compare_files(file_a, file_b) {
+                    conflicts.push(FileConflict {
+                        path: path.clone(),
+                        package: nevras[*pkg_a].clone(),
+                        other_package: nevras[*pkg_b].clone(),
+                        kind,
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(conflicts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FileOptions, PackageBuilder};
+
+    #[test]
+    fn test_check_file_conflicts() -> Result<(), Box<dyn std::error::Error>> {
+        let package = |name: &str| PackageBuilder::new(name, "1.0", "MIT", "noarch", name);
+
+        let a = package("a")
+            .with_file(
+                "./test_assets/awesome.toml",
+                FileOptions::new("/etc/awesome/config.toml").mode(0o100644),
+            )?
+            .with_file(
+                "./test_assets/awesome.py",
+                FileOptions::new("/usr/bin/awesome").mode(0o100755),
+            )?
+            .with_file(
+                "./test_assets/awesome.xml",
+                FileOptions::new("/var/lib/awesome/state").is_ghost(),
+            )?
+            .build()?
+            .metadata;
+        let b = package("b")
+            // identical files and directories may be shared
+            .with_file(
+                "./test_assets/awesome.toml",
+                FileOptions::new("/etc/awesome/config.toml").mode(0o100644),
+            )?
+            .with_file(
+                "./test_assets/awesome.toml",
+                FileOptions::new("/var/lib/awesome/state"),
+            )?
+            .build()?
+            .metadata;
+        assert_eq!(check_file_conflicts([&a, &b])?, []);
+
+        let c = package("c")
+            .with_file(
+                "./test_assets/awesome.xml",
+                FileOptions::new("/etc/awesome/config.toml").mode(0o100644),
+            )?
+            .with_file(
+                "./test_assets/awesome.py",
+                FileOptions::new("/usr/bin/awesome")
+                    .mode(0o100755)
+                    .user("awesome"),
+            )?
+            .build()?
+            .metadata;
+        let conflicts = check_file_conflicts([&a, &b, &c])?;
+        let conflicts: Vec<String> = conflicts.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            conflicts,
+            [
+                "file /etc/awesome/config.toml conflicts between attempted installs of a-0:1.0-1.noarch and c-0:1.0-1.noarch (contents differs)",
+                "file /etc/awesome/config.toml conflicts between attempted installs of b-0:1.0-1.noarch and c-0:1.0-1.noarch (contents differs)",
+                "file /usr/bin/awesome conflicts between attempted installs of a-0:1.0-1.noarch and c-0:1.0-1.noarch (owner differs)",
+            ]
+        );
+
+        let d = package("d")
+            .with_file(
+                "./test_assets/awesome.py",
+                FileOptions::new("/usr/bin/awesome").mode(0o100700),
+            )?
+            .with_file(
+                "./test_assets/empty_file_for_symlink_create",
+                FileOptions::new("/etc/awesome/config.toml")
+                    .mode(0o120644)
+                    .symlink("/usr/share/awesome/config.toml"),
+            )?
+            .build()?
+            .metadata;
+        let conflicts = check_file_conflicts([&a, &d])?;
+        let kinds: Vec<_> = conflicts.iter().map(|c| c.kind).collect();
+        assert_eq!(kinds, [FileConflictKind::FileType, FileConflictKind::Mode]);
+        Ok(())
+    }
+}
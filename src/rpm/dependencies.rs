@@ -0,0 +1,566 @@
+//! Checks of the dependencies between a set of packages, and the order to install them in,
+//! without the help of a package manager.
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap, HashMap},
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use crate::{errors::*, Dependency, DependencyFlags, Nevra, PackageMetadata};
+
+/// A problem found by [`check_dependencies`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DependencyProblem {
+    /// No package in the set provides something required by `package`
+    Unsatisfied {
+        package: Nevra,
+        requirement: Dependency,
+    },
+    /// `package` conflicts with another package in the set
+    Conflict {
+        package: Nevra,
+        conflict: Dependency,
+        conflicting: Nevra,
+    },
+    /// `package` obsoletes another package in the set
+    Obsoleted {
+        package: Nevra,
+        obsoletes: Dependency,
+        obsoleted: Nevra,
+    },
+}
+
+impl Display for DependencyProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DependencyProblem::Unsatisfied {
+                package,
+                requirement,
+            } => write!(f, "{} is needed by {}", requirement, package),
+            DependencyProblem::Conflict {
+                package,
+                conflict,
+                conflicting,
+            } => write!(
+                f,
+                "{} conflicts with {} ({})",
+                package, conflicting, conflict
+            ),
+            DependencyProblem::Obsoleted {
+                package,
+                obsoletes,
+                obsoleted,
+            } => write!(
+                f,
+                "{} is obsoleted by {} ({})",
+                obsoleted, package, obsoletes
+            ),
+        }
+    }
+}
+
+/// The dependency related information of a single package.
+struct PackageDependencies {
+    nevra: Nevra,
+    /// `name = [epoch:]version-release`, which is what obsoletes are matched against
+    self_provide: Dependency,
+    provides: Vec<Dependency>,
+    requires: Vec<Dependency>,
+    conflicts: Vec<Dependency>,
+    obsoletes: Vec<Dependency>,
+    order_with_requires: Vec<Dependency>,
+    files: Vec<PathBuf>,
+}
+
+impl PackageDependencies {
+    fn new(metadata: &PackageMetadata) -> Result<Self, Error> {
+        let nevra = metadata.get_nevra()?;
+        let self_provide = Dependency::eq(nevra.name.clone(), nevra.evr().to_string());
+
+        Ok(PackageDependencies {
+            nevra,
+            self_provide,
+            provides: metadata.get_provides()?,
+            requires: metadata.get_requires()?,
+            conflicts: metadata.get_conflicts()?,
+            obsoletes: metadata.get_obsoletes()?,
+            order_with_requires: metadata.get_order_with_requires()?,
+            files: metadata.get_file_paths()?,
+        })
+    }
+}
+
+/// A set of packages, indexed by what they provide.
+struct PackageSet {
+    packages: Vec<PackageDependencies>,
+    providers: HashMap<String, Vec<(usize, Dependency)>>,
+    file_owners: HashMap<PathBuf, Vec<usize>>,
+}
+
+impl PackageSet {
+    fn new<'a>(packages: impl IntoIterator<Item = &'a PackageMetadata>) -> Result<Self, Error> {
+        let packages = packages
+            .into_iter()
+            .map(PackageDependencies::new)
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let mut providers: HashMap<String, Vec<(usize, Dependency)>> = HashMap::new();
+        let mut file_owners: HashMap<PathBuf, Vec<usize>> = HashMap::new();
+        for (idx, package) in packages.iter().enumerate() {
+            // rpmbuild always adds the self-provide explicitly, but don't rely on it
+            for provide in package.provides.iter().chain([&package.self_provide]) {
+                providers
+                    .entry(provide.name.clone())
+                    .or_default()
+                    .push((idx, provide.clone()));
+            }
+            for file in &package.files {
+                file_owners.entry(file.clone()).or_default().push(idx);
+            }
+        }
+
+        Ok(PackageSet {
+            packages,
+            providers,
+            file_owners,
+        })
+    }
+
+    /// The indices of the packages matching the dependency, in ascending order.
+    fn matching(&self, dependency: &Dependency) -> Vec<usize> {
+        let mut matches: Vec<usize> = self
+            .providers
+            .get(&dependency.name)
+            .into_iter()
+            .flatten()
+            .filter(|(_, provide)| dependency.overlaps(provide))
+            .map(|(idx, _)| *idx)
+            .collect();
+        if dependency.name.starts_with('/') {
+            if let Some(owners) = self.file_owners.get(Path::new(&dependency.name)) {
+                matches.extend(owners);
+            }
+        }
+        matches.sort_unstable();
+        matches.dedup();
+        matches
+    }
+}
+
+/// Whether a dependency can't be resolved against a set of packages, as it is either provided by
+/// rpm itself, or a rich (boolean) dependency.
+fn is_unresolvable(dependency: &Dependency) -> bool {
+    dependency.flags.contains(DependencyFlags::RPMLIB)
+        || dependency.name.starts_with("rpmlib(")
+        || dependency.name.starts_with('(')
+}
+
+/// Check that the requirements of every package are satisfied within the given set of packages,
+/// and that no package conflicts with or obsoletes another one of them.
+///
+/// Requirements are matched against the provides of all packages using rpm's range semantics
+/// (see [`Dependency::overlaps`]). Requirements on file paths are also satisfied by any package
+/// containing that file. `rpmlib()` requirements are left to rpm itself and are not checked, and
+/// neither are rich (boolean) dependencies such as `(foo if bar)`.
+///
+/// Returns an empty list if no problems were found.
+pub fn check_dependencies<'a>(
+    packages: impl IntoIterator<Item = &'a PackageMetadata>,
+) -> Result<Vec<DependencyProblem>, Error> {
+    let set = PackageSet::new(packages)?;
+    let packages = &set.packages;
+
+    let mut problems = Vec::new();
+    for (idx, package) in packages.iter().enumerate() {
+        for requirement in &package.requires {
+            if is_unresolvable(requirement) {
+                continue;
+            }
+            if set.matching(requirement).is_empty() {
+                problems.push(DependencyProblem::Unsatisfied {
+                    package: package.nevra.clone(),
+                    requirement: requirement.clone(),
+                });
+            }
+        }
+
+        for conflict in &package.conflicts {
+            if is_unresolvable(conflict) {
+                continue;
+            }
+            for other in set
+                .matching(conflict)
+                .into_iter()
+                .filter(|other| *other != idx)
+            {
+                problems.push(DependencyProblem::Conflict {
+                    package: package.nevra.clone(),
+                    conflict: conflict.clone(),
+                    conflicting: packages[other].nevra.clone(),
+                });
+            }
+        }
+
+        // obsoletes only ever match package names, not other provides
+        for obsoletes in &package.obsoletes {
+            for (other_idx, other) in packages.iter().enumerate() {
+                if other_idx != idx && obsoletes.overlaps(&other.self_provide) {
+                    problems.push(DependencyProblem::Obsoleted {
+                        package: package.nevra.clone(),
+                        obsoletes: obsoletes.clone(),
+                        obsoleted: other.nevra.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(problems)
+}
+
+/// The result of [`compute_install_order`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstallOrder {
+    /// Indices into the given packages, in the order they should be installed in
+    pub order: Vec<usize>,
+    /// The dependency loops which had to be broken to compute the order
+    pub cycles: Vec<DependencyCycle>,
+}
+
+/// A group of packages which (directly or indirectly) require each other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyCycle {
+    /// Indices of the packages forming the loop, in ascending order
+    pub packages: Vec<usize>,
+    /// Whether a pre-requisite (e.g. `Requires(pre)`) had to be ignored to break the loop,
+    /// meaning a scriptlet may run before a package it requires is installed
+    pub broke_prerequisite: bool,
+}
+
+/// Whether a requirement has to be installed before the scriptlets of the package requiring it
+/// are run, as opposed to only being needed at some point during the transaction.
+pub(crate) fn is_prerequisite(requirement: &Dependency) -> bool {
+    requirement.flags.intersects(
+        DependencyFlags::PREREQ
+            | DependencyFlags::SCRIPT_PRE
+            | DependencyFlags::SCRIPT_POST
+            | DependencyFlags::PRETRANS
+            | DependencyFlags::POSTTRANS,
+    )
+}
+
+/// Compute the order in which rpm would install the given packages.
+///
+/// Packages are sorted topologically, with every package being installed after the packages
+/// providing its requirements. Ordering hints (`OrderWithRequires`) are honoured the same way,
+/// except that they don't need to be satisfied.
+///
+/// Dependency loops are broken like rpm does it: requirements only needed at runtime are ignored
+/// first, and pre-requisites (`Requires(pre)`, `Requires(post)`, `Requires(pretrans)`, ...) only
+/// if there is no other way. Every loop is reported in [`InstallOrder::cycles`].
+///
+/// The result is deterministic, packages which don't depend on each other keep their relative
+/// order from the input.
+pub fn compute_install_order<'a>(
+    packages: impl IntoIterator<Item = &'a PackageMetadata>,
+) -> Result<InstallOrder, Error> {
+    let set = PackageSet::new(packages)?;
+    let count = set.packages.len();
+
+    // for each package, the packages which need to be installed before it, and whether any of
+    // those relations is a pre-requisite
+    let mut predecessors: Vec<BTreeMap<usize, bool>> = vec![BTreeMap::new(); count];
+    for (idx, package) in set.packages.iter().enumerate() {
+        let requires = package.requires.iter().map(|r| (r, is_prerequisite(r)));
+        let hints = package.order_with_requires.iter().map(|r| (r, false));
+        for (dependency, prerequisite) in requires.chain(hints) {
+            if is_unresolvable(dependency) {
+                continue;
+            }
+            for provider in set.matching(dependency) {
+                if provider != idx {
+                    *predecessors[idx].entry(provider).or_default() |= prerequisite;
+                }
+            }
+        }
+    }
+    let mut successors: Vec<Vec<usize>> = vec![Vec::new(); count];
+    for (idx, preds) in predecessors.iter().enumerate() {
+        for pred in preds.keys() {
+            successors[*pred].push(idx);
+        }
+    }
+
+    let components = strongly_connected_components(&successors);
+    let mut component_of = vec![0; count];
+    for (component_idx, component) in components.iter().enumerate() {
+        for idx in component {
+            component_of[*idx] = component_idx;
+        }
+    }
+
+    // topologically sort the loops (strongly connected components), preferring the original
+    // order of the packages where there's a choice
+    let mut pending_relations = vec![0usize; components.len()];
+    for (idx, preds) in predecessors.iter().enumerate() {
+        pending_relations[component_of[idx]] += preds
+            .keys()
+            .filter(|pred| component_of[**pred] != component_of[idx])
+            .count();
+    }
+    let mut ready: BinaryHeap<Reverse<(usize, usize)>> = components
+        .iter()
+        .enumerate()
+        .filter(|(component_idx, _)| pending_relations[*component_idx] == 0)
+        .map(|(component_idx, component)| Reverse((component[0], component_idx)))
+        .collect();
+
+    let mut order = Vec::with_capacity(count);
+    let mut cycles = Vec::new();
+    while let Some(Reverse((_, component_idx))) = ready.pop() {
+        let component = &components[component_idx];
+        let start = order.len();
+        if component.len() == 1 {
+            order.push(component[0]);
+        } else {
+            let broke_prerequisite = order_cycle(component, &predecessors, &mut order);
+            cycles.push(DependencyCycle {
+                packages: component.clone(),
+                broke_prerequisite,
+            });
+        }
+
+        for idx in &order[start..] {
+            for succ in &successors[*idx] {
+                let succ_component = component_of[*succ];
+                if succ_component != component_idx {
+                    pending_relations[succ_component] -= 1;
+                    if pending_relations[succ_component] == 0 {
+                        let first = components[succ_component][0];
+                        ready.push(Reverse((first, succ_component)));
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(InstallOrder { order, cycles })
+}
+
+/// Order the packages of a dependency loop, by repeatedly picking the package with the fewest
+/// pre-requisites, then the fewest other requirements not yet installed.
+///
+/// Returns whether a pre-requisite had to be ignored.
+fn order_cycle(
+    component: &[usize],
+    predecessors: &[BTreeMap<usize, bool>],
+    order: &mut Vec<usize>,
+) -> bool {
+    let mut broke_prerequisite = false;
+    let mut remaining = component.to_vec();
+    while !remaining.is_empty() {
+        let (pos, (prerequisites, _, _)) = remaining
+            .iter()
+            .map(|idx| {
+                let (prerequisites, requirements) = predecessors[*idx]
+                    .iter()
+                    .filter(|(pred, _)| remaining.contains(pred))
+                    .fold((0, 0), |(pre, req), (_, prerequisite)| {
+                        if *prerequisite {
+                            (pre + 1, req)
+                        } else {
+                            (pre, req + 1)
+                        }
+                    });
+                (prerequisites, requirements, *idx)
+            })
+            .enumerate()
+            .min_by_key(|(_, key)| *key)
+            .expect("remaining packages is not empty");
+        broke_prerequisite |= prerequisites > 0;
+        order.push(remaining.remove(pos));
+    }
+    broke_prerequisite
+}
+
+/// Tarjan's algorithm, iteratively. Every component is sorted, and the components are returned
+/// in reverse topological order.
+fn strongly_connected_components(successors: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let count = successors.len();
+    let mut index = vec![usize::MAX; count];
+    let mut lowlink = vec![0; count];
+    let mut on_stack = vec![false; count];
+    let mut stack = Vec::new();
+    let mut components = Vec::new();
+    let mut next_index = 0;
+
+    for root in 0..count {
+        if index[root] != usize::MAX {
+            continue;
+        }
+        let mut calls = vec![(root, 0)];
+        index[root] = next_index;
+        lowlink[root] = next_index;
+        next_index += 1;
+        stack.push(root);
+        on_stack[root] = true;
+
+        while let Some(&(node, edge)) = calls.last() {
+            if let Some(&succ) = successors[node].get(edge) {
+                calls.last_mut().unwrap().1 += 1;
+                if index[succ] == usize::MAX {
+                    index[succ] = next_index;
+                    lowlink[succ] = next_index;
+                    next_index += 1;
+                    stack.push(succ);
+                    on_stack[succ] = true;
+                    calls.push((succ, 0));
+                } else if on_stack[succ] {
+                    lowlink[node] = lowlink[node].min(index[succ]);
+                }
+                continue;
+            }
+
+            calls.pop();
+            if let Some(&(parent, _)) = calls.last() {
+                lowlink[parent] = lowlink[parent].min(lowlink[node]);
+            }
+            if lowlink[node] == index[node] {
+                let mut component = Vec::new();
+                while let Some(member) = stack.pop() {
+                    on_stack[member] = false;
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                component.sort_unstable();
+                components.push(component);
+            }
+        }
+    }
+    components
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FileOptions, PackageBuilder};
+
+    fn build(builder: PackageBuilder) -> PackageMetadata {
+        builder.build().unwrap().metadata
+    }
+
+    #[test]
+    fn test_check_dependencies() -> Result<(), Box<dyn std::error::Error>> {
+        let lib = build(
+            PackageBuilder::new("libfoo", "2.1", "MIT", "x86_64", "a library")
+                .release("3")
+                .provides(Dependency::eq("libfoo.so.2()(64bit)", ""))
+                .with_file("Cargo.toml", FileOptions::new("/usr/share/foo/data"))?,
+        );
+        let app = build(
+            PackageBuilder::new("foo", "1.0", "MIT", "x86_64", "an application")
+                .requires(Dependency::greater_eq("libfoo", "2.0"))
+                .requires(Dependency::any("libfoo.so.2()(64bit)"))
+                .requires(Dependency::any("/usr/share/foo/data"))
+                .requires(Dependency::rpmlib("SomeFeature", "1.0")),
+        );
+        assert_eq!(check_dependencies([&lib, &app])?, []);
+
+        let problems = check_dependencies([&app])?;
+        let problems: Vec<String> = problems.iter().map(|p| p.to_string()).collect();
+        assert_eq!(
+            problems,
+            [
+                "libfoo >= 2.0 is needed by foo-0:1.0-1.x86_64",
+                "libfoo.so.2()(64bit) is needed by foo-0:1.0-1.x86_64",
+                "/usr/share/foo/data is needed by foo-0:1.0-1.x86_64",
+            ]
+        );
+
+        let new_app = build(
+            PackageBuilder::new("foo", "1.0", "MIT", "x86_64", "an application")
+                .requires(Dependency::greater_eq("libfoo", "2.2")),
+        );
+        assert_eq!(
+            check_dependencies([&lib, &new_app])?,
+            [DependencyProblem::Unsatisfied {
+                package: new_app.get_nevra()?,
+                requirement: Dependency::greater_eq("libfoo", "2.2"),
+            }]
+        );
+
+        let conflicting = build(
+            PackageBuilder::new("bar", "1.0", "MIT", "noarch", "conflicts with foo")
+                .conflicts(Dependency::less("libfoo", "3.0"))
+                .conflicts(Dependency::any("/usr/share/foo/data"))
+                .obsoletes(Dependency::less("foo", "1.0-2")),
+        );
+        let problems = check_dependencies([&lib, &app, &conflicting])?;
+        let problems: Vec<String> = problems.iter().map(|p| p.to_string()).collect();
+        assert_eq!(
+            problems,
+            [
+                "bar-0:1.0-1.noarch conflicts with libfoo-0:2.1-3.x86_64 (libfoo < 3.0)",
+                "bar-0:1.0-1.noarch conflicts with libfoo-0:2.1-3.x86_64 (/usr/share/foo/data)",
+                "foo-0:1.0-1.x86_64 is obsoleted by bar-0:1.0-1.noarch (foo < 1.0-2)",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_compute_install_order() -> Result<(), Box<dyn std::error::Error>> {
+        let package = |name: &str| PackageBuilder::new(name, "1.0", "MIT", "noarch", name);
+
+        let packages = [
+            build(package("app").requires(Dependency::any("lib"))),
+            build(package("unrelated")),
+            build(package("lib").requires(Dependency::script_pre("setup"))),
+            build(
+                package("hinted")
+                    .order_with_requires(Dependency::any("app"))
+                    .order_with_requires(Dependency::any("missing")),
+            ),
+            build(package("setup")),
+        ];
+        let install_order = compute_install_order(&packages)?;
+        assert_eq!(install_order.order, [1, 4, 2, 0, 3]);
+        assert_eq!(install_order.cycles, []);
+
+        // the runtime requirement is ignored to break the loop
+        let packages = [
+            build(package("x").requires(Dependency::any("y"))),
+            build(package("y").requires(Dependency::script_post("x"))),
+            build(package("z").requires(Dependency::any("y"))),
+        ];
+        let install_order = compute_install_order(&packages)?;
+        assert_eq!(install_order.order, [0, 1, 2]);
+        assert_eq!(
+            install_order.cycles,
+            [DependencyCycle {
+                packages: vec![0, 1],
+                broke_prerequisite: false,
+            }]
+        );
+
+        let packages = [
+            build(package("z").requires(Dependency::any("y"))),
+            build(package("x").requires(Dependency::script_pre("y"))),
+            build(package("y").requires(Dependency::script_pre("x"))),
+        ];
+        let install_order = compute_install_order(&packages)?;
+        assert_eq!(install_order.order, [1, 2, 0]);
+        assert_eq!(
+            install_order.cycles,
+            [DependencyCycle {
+                packages: vec![1, 2],
+                broke_prerequisite: true,
+            }]
+        );
+        Ok(())
+    }
+}
@@ -90,7 +90,7 @@ where
                 IndexData::I18NString(ref mut strings) => {
                     for _ in 0..entry.num_items {
                         let (rest, raw_string) = complete::take_till(|item| item == 0)(remaining)?;
-                        remaining = rest;
+                        remaining = &rest[1..];
                         let string = String::from_utf8_lossy(raw_string).to_string();
                         strings.push(string);
                     }
@@ -329,6 +329,61 @@ impl fmt::Display for Header<IndexTag> {
     }
 }
 
+impl<T> Header<T>
+where
+    T: Tag,
+{
+    /// Set the data of an entry, adding the entry if it isn't present yet, and regenerate the
+    /// header with the given region tag.
+    fn set_entry_in_region(&mut self, tag: T, data: IndexData, region_tag: T) {
+        let entry = IndexEntry::new(tag, 0, data);
+        match self
+            .index_entries
+            .iter_mut()
+            .find(|entry| entry.tag == tag.to_u32())
+        {
+            Some(existing) => *existing = entry,
+            None => self.index_entries.push(entry),
+        }
+        self.regenerate(region_tag);
+    }
+
+    /// Remove an entry, and regenerate the header with the given region tag.
+    fn remove_entry_in_region(&mut self, tag: T, region_tag: T) -> Result<(), Error> {
+        let index = self
+            .index_entries
+            .iter()
+            .position(|entry| entry.tag == tag.to_u32())
+            .ok_or_else(|| Error::TagNotFound(tag.to_string()))?;
+        self.index_entries.remove(index);
+        self.regenerate(region_tag);
+        Ok(())
+    }
+
+    /// Rebuild the store and the region from the data of the entries, e.g. after entries were
+    /// modified.
+    fn regenerate(&mut self, region_tag: T) {
+        let entries = std::mem::take(&mut self.index_entries)
+            .into_iter()
+            .filter(|entry| entry.tag != region_tag.to_u32())
+            .collect();
+        *self = Self::from_entries(entries, region_tag);
+    }
+}
+
+impl Header<IndexTag> {
+    /// Set the data of an entry, adding the entry if it isn't present yet, and regenerate the
+    /// header.
+    pub(crate) fn set_entry(&mut self, tag: IndexTag, data: IndexData) {
+        self.set_entry_in_region(tag, data, IndexTag::RPMTAG_HEADERIMMUTABLE);
+    }
+
+    /// Remove an entry, and regenerate the header.
+    pub(crate) fn remove_entry(&mut self, tag: IndexTag) -> Result<(), Error> {
+        self.remove_entry_in_region(tag, IndexTag::RPMTAG_HEADERIMMUTABLE)
+    }
+}
+
 impl Header<IndexSignatureTag> {
     pub fn builder() -> SignatureHeaderBuilder<Empty> {
         SignatureHeaderBuilder::<Empty>::new()
@@ -359,6 +414,17 @@ impl Header<IndexSignatureTag> {
         Ok(())
     }
 
+    /// Set the data of an entry, adding the entry if it isn't present yet, and regenerate the
+    /// header.
+    pub(crate) fn set_entry(&mut self, tag: IndexSignatureTag, data: IndexData) {
+        self.set_entry_in_region(tag, data, IndexSignatureTag::HEADER_SIGNATURES);
+    }
+
+    /// Remove an entry, and regenerate the header.
+    pub(crate) fn remove_entry(&mut self, tag: IndexSignatureTag) -> Result<(), Error> {
+        self.remove_entry_in_region(tag, IndexSignatureTag::HEADER_SIGNATURES)
+    }
+
     pub fn new_empty() -> Self {
         Self {
             index_header: IndexHeader::new(0, 0),
@@ -402,7 +468,7 @@ impl FileDigest {
         Ok(match algorithm {
             DigestAlgorithm::Md5 if digest.digest.len() == 32 => digest,
             DigestAlgorithm::Sha2_256 if digest.digest.len() == 64 => digest,
-            DigestAlgorithm::Sha2_224 if digest.digest.len() == 60 => digest,
+            DigestAlgorithm::Sha2_224 if digest.digest.len() == 56 => digest,
             DigestAlgorithm::Sha2_384 if digest.digest.len() == 96 => digest,
             DigestAlgorithm::Sha2_512 if digest.digest.len() == 128 => digest,
             // @todo disambiguate mismatch of length from unsupported algorithm
@@ -435,6 +501,18 @@ pub struct ChangelogEntry {
     pub description: String,
 }
 
+/// User facing accessor type for a source or patch of a source package
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct SourceFile {
+    /// File name of the source or patch
+    pub name: String,
+    /// The `N` of `SourceN` / `PatchN`, if known. rpm doesn't record it, see
+    /// [`PackageMetadata::get_sources`](crate::PackageMetadata::get_sources).
+    pub number: Option<u32>,
+    /// Whether the file was left out of the package (`NoSource` / `NoPatch`)
+    pub excluded: bool,
+}
+
 /// User facing accessor type for a file entry with contextual information
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
 pub struct FileEntry {
@@ -649,6 +727,55 @@ impl<T: Tag> fmt::Display for IndexEntry<T> {
     }
 }
 
+/// A typed value of a header entry, used for editing the header of a package with
+/// [`PackageMetadata::set_entry`](crate::PackageMetadata::set_entry).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagValue {
+    Char(Vec<u8>),
+    Int8(Vec<u8>),
+    Int16(Vec<u16>),
+    Int32(Vec<u32>),
+    Int64(Vec<u64>),
+    String(String),
+    Binary(Vec<u8>),
+    StringArray(Vec<String>),
+    I18NString(Vec<String>),
+}
+
+impl From<&str> for TagValue {
+    fn from(value: &str) -> Self {
+        TagValue::String(value.to_owned())
+    }
+}
+
+impl From<String> for TagValue {
+    fn from(value: String) -> Self {
+        TagValue::String(value)
+    }
+}
+
+impl From<u32> for TagValue {
+    fn from(value: u32) -> Self {
+        TagValue::Int32(vec![value])
+    }
+}
+
+impl From<TagValue> for IndexData {
+    fn from(value: TagValue) -> Self {
+        match value {
+            TagValue::Char(v) => IndexData::Char(v),
+            TagValue::Int8(v) => IndexData::Int8(v),
+            TagValue::Int16(v) => IndexData::Int16(v),
+            TagValue::Int32(v) => IndexData::Int32(v),
+            TagValue::Int64(v) => IndexData::Int64(v),
+            TagValue::String(v) => IndexData::StringTag(v),
+            TagValue::Binary(v) => IndexData::Bin(v),
+            TagValue::StringArray(v) => IndexData::StringArray(v),
+            TagValue::I18NString(v) => IndexData::I18NString(v),
+        }
+    }
+}
+
 /// Data as present in a [`IndexEntry`](self::IndexEntry) .
 #[derive(Debug, PartialEq, Eq)]
 pub(crate) enum IndexData {
@@ -89,7 +89,7 @@ impl Lead {
         Ok(())
     }
 
-    pub(crate) fn new(name: &str) -> Self {
+    pub(crate) fn new(name: &str, is_source: bool) -> Self {
         let mut name_arr = [0; 66];
         // the last byte needs to be the null terminator
         let name_size = std::cmp::min(name_arr.len() - 1, name.len());
@@ -99,7 +99,7 @@ impl Lead {
             magic: RPM_MAGIC,
             major: 3,
             minor: 0,
-            package_type: 0,
+            package_type: if is_source { 1 } else { 0 },
             arch: 0,
             name: name_arr,
             os: 1,
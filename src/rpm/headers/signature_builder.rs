@@ -4,6 +4,7 @@ use super::*;
 
 use super::IndexEntry;
 use crate::constants::*;
+use base64::Engine;
 use std::default::Default;
 
 /// A marker trait for builder stages
@@ -76,6 +77,30 @@ where
             IndexSignatureTag::HEADER_SIGNATURES,
         )
     }
+
+    /// Append a signature to the rpm 6 `RPMSIGTAG_OPENPGP` tag.
+    fn push_openpgp_signature(&mut self, sig_header_only: &[u8]) {
+        let encoded = base64::engine::general_purpose::STANDARD.encode(sig_header_only);
+        let existing = self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.tag == IndexSignatureTag::RPMSIGTAG_OPENPGP.to_u32());
+        match existing {
+            Some(IndexEntry {
+                data: IndexData::StringArray(signatures),
+                num_items,
+                ..
+            }) => {
+                signatures.push(encoded);
+                *num_items += 1;
+            }
+            _ => self.entries.push(IndexEntry::new(
+                IndexSignatureTag::RPMSIGTAG_OPENPGP,
+                0i32,
+                IndexData::StringArray(vec![encoded]),
+            )),
+        }
+    }
 }
 
 impl SignatureHeaderBuilder<Empty> {
@@ -110,6 +135,16 @@ impl SignatureHeaderBuilder<Empty> {
 }
 
 impl SignatureHeaderBuilder<WithDigest> {
+    /// add the SHA3-256 digest of the header, as defined by rpm 6
+    pub fn add_sha3_256_digest(mut self, digest_header_sha3_256: &str) -> Self {
+        self.entries.push(IndexEntry::new(
+            IndexSignatureTag::RPMSIGTAG_SHA3_256,
+            0i32,
+            IndexData::StringTag(digest_header_sha3_256.to_string()),
+        ));
+        self
+    }
+
     /// add a signature over the header and a signature across header and source excluding the static lead
     pub fn add_rsa_signature(
         mut self,
@@ -148,6 +183,28 @@ impl SignatureHeaderBuilder<WithDigest> {
             phantom: Default::default(),
         }
     }
+
+    /// add a signature over the header to the rpm 6 `RPMSIGTAG_OPENPGP` tag, which is ignored by
+    /// older versions of rpm
+    pub fn add_openpgp_signature(
+        mut self,
+        sig_header_only: &[u8],
+    ) -> SignatureHeaderBuilder<WithSignature> {
+        self.push_openpgp_signature(sig_header_only);
+        SignatureHeaderBuilder::<WithSignature> {
+            entries: self.entries,
+            phantom: Default::default(),
+        }
+    }
+}
+
+impl SignatureHeaderBuilder<WithSignature> {
+    /// add another signature over the header to the rpm 6 `RPMSIGTAG_OPENPGP` tag, e.g. to sign a
+    /// package with both the old and the new key during a key rotation
+    pub fn add_openpgp_signature(mut self, sig_header_only: &[u8]) -> Self {
+        self.push_openpgp_signature(sig_header_only);
+        self
+    }
 }
 
 #[cfg(test)]
@@ -225,6 +282,46 @@ mod test {
             .is_ok());
     }
 
+    #[test]
+    fn signature_builder_w_multiple_signatures() {
+        let builder = SignatureHeaderBuilder::<Empty>::new();
+
+        let digest_header_sha1 = hex::encode([0u8; 64]);
+        let digest_header_sha256: String = hex::encode([0u8; 64]);
+        let digest_header_sha3_256: String = hex::encode([0u8; 64]);
+
+        let digest_header_and_archive = [0u8; 64];
+
+        let header = builder
+            .add_digest(
+                digest_header_sha1.as_str(),
+                digest_header_sha256.as_str(),
+                &digest_header_and_archive[..],
+            )
+            .add_sha3_256_digest(digest_header_sha3_256.as_str())
+            .add_rsa_signature(&[1u8; 32][..], &[2u8; 32][..])
+            .add_openpgp_signature(&[1u8; 32][..])
+            .add_openpgp_signature(&[3u8; 32][..])
+            .build(32);
+
+        assert!(header
+            .find_entry_or_err(IndexSignatureTag::RPMSIGTAG_RSA)
+            .is_ok());
+        assert!(header
+            .find_entry_or_err(IndexSignatureTag::RPMSIGTAG_SHA3_256)
+            .is_ok());
+        let signatures = header
+            .get_entry_data_as_string_array(IndexSignatureTag::RPMSIGTAG_OPENPGP)
+            .unwrap();
+        assert_eq!(
+            signatures,
+            [
+                base64::engine::general_purpose::STANDARD.encode([1u8; 32]),
+                base64::engine::general_purpose::STANDARD.encode([3u8; 32]),
+            ]
+        );
+    }
+
     #[test]
     fn signature_builder_digest_only() {
         let builder = SignatureHeaderBuilder::<Empty>::new();
@@ -1,8 +1,8 @@
 //! A collection of types used in various header records.
-use crate::{constants::*, errors, FileCaps, IndexData, IndexEntry, Timestamp};
+use crate::{constants::*, errors, Evr, FileCaps, IndexData, IndexEntry, Timestamp};
 use digest::Digest;
 use itertools::Itertools;
-use std::str::FromStr;
+use std::{cmp::Ordering, str::FromStr};
 
 /// Offsets into an RPM Package (from the start of the file) demarking locations of each section
 ///
@@ -347,7 +347,7 @@ impl From<FileOptionsBuilder> for FileOptions {
 }
 
 /// Description of a dependency as present in a RPM header record.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Dependency {
     pub name: String,
     pub flags: DependencyFlags,
@@ -476,6 +476,81 @@ impl Dependency {
             version,
         }
     }
+
+    /// Whether the version ranges of this dependency and `other` overlap, following rpm's rules.
+    ///
+    /// Dependencies with different names never overlap. If either side doesn't constrain the
+    /// version (e.g. `Requires: foo`), they always overlap. A missing epoch counts as `0`, and the
+    /// release is only compared if both sides specify one, so `foo >= 1.0` is satisfied by
+    /// `foo = 1.0-3`.
+    pub fn overlaps(&self, other: &Dependency) -> bool {
+        if self.name != other.name {
+            return false;
+        }
+
+        let sense = DependencyFlags::LESS | DependencyFlags::GREATER | DependencyFlags::EQUAL;
+        let flags1 = self.flags & sense;
+        let flags2 = other.flags & sense;
+        if flags1.is_empty() || flags2.is_empty() {
+            return true;
+        }
+        if self.version.is_empty() || other.version.is_empty() {
+            return true;
+        }
+
+        let (Ok(evr1), Ok(evr2)) = (self.version.parse::<Evr>(), other.version.parse::<Evr>())
+        else {
+            return false;
+        };
+
+        match evr1.cmp_for_dependency(&evr2) {
+            Ordering::Less => {
+                flags1.contains(DependencyFlags::GREATER) || flags2.contains(DependencyFlags::LESS)
+            }
+            Ordering::Greater => {
+                flags1.contains(DependencyFlags::LESS) || flags2.contains(DependencyFlags::GREATER)
+            }
+            Ordering::Equal => !(flags1 & flags2).is_empty(),
+        }
+    }
+
+    /// Whether this dependency is satisfied by (overlaps with) any of the given provides.
+    ///
+    /// ```
+    /// use rpm::Dependency;
+    ///
+    /// let provides = [Dependency::eq("foo", "1:2.0-3"), Dependency::any("bar")];
+    /// assert!(Dependency::greater_eq("foo", "1:2.0").is_satisfied_by(&provides));
+    /// assert!(Dependency::less("bar", "1.0").is_satisfied_by(&provides));
+    /// assert!(!Dependency::greater("foo", "1:2.0-3").is_satisfied_by(&provides));
+    /// assert!(!Dependency::less_eq("foo", "2.0").is_satisfied_by(&provides));
+    /// ```
+    pub fn is_satisfied_by<'a>(&self, provides: impl IntoIterator<Item = &'a Dependency>) -> bool {
+        provides.into_iter().any(|provide| self.overlaps(provide))
+    }
+}
+
+impl std::fmt::Display for Dependency {
+    /// Format the dependency the way it would be written in a spec file, e.g. `foo >= 1.0`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)?;
+        let op = match (
+            self.flags.contains(DependencyFlags::LESS),
+            self.flags.contains(DependencyFlags::GREATER),
+            self.flags.contains(DependencyFlags::EQUAL),
+        ) {
+            (true, false, false) => "<",
+            (true, false, true) => "<=",
+            (false, true, false) => ">",
+            (false, true, true) => ">=",
+            (false, false, true) => "=",
+            _ => return Ok(()),
+        };
+        if !self.version.is_empty() {
+            write!(f, " {} {}", op, self.version)?;
+        }
+        Ok(())
+    }
 }
 
 /// A wrapper for calculating the sha256 checksum of the contents written to it
@@ -508,6 +583,38 @@ impl<W: std::io::Write> std::io::Write for Sha256Writer<W> {
     }
 }
 
+/// A wrapper for calculating a checksum of the contents written to it with any of the supported
+/// digest algorithms
+pub(crate) struct DigestWriter<W> {
+    writer: W,
+    hasher: Box<dyn digest::DynDigest>,
+}
+
+impl<W> DigestWriter<W> {
+    pub(crate) fn new(writer: W, algorithm: DigestAlgorithm) -> Self {
+        DigestWriter {
+            writer,
+            hasher: algorithm.hasher(),
+        }
+    }
+
+    pub(crate) fn into_hex_digest(self) -> String {
+        hex::encode(self.hasher.finalize())
+    }
+}
+
+impl<W: std::io::Write> std::io::Write for DigestWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.writer.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
 /// Type-alias for a tuple containing index tags for a scriptlet type,
 ///
 pub(crate) type ScriptletIndexTags = (IndexTag, IndexTag, IndexTag);
@@ -761,4 +868,94 @@ echo `hello world`
 
         ()
     }
+
+    #[test]
+    fn test_dependency_overlaps() {
+        use super::*;
+
+        let test_table = [
+            (Dependency::any("foo"), Dependency::eq("foo", "1.0"), true),
+            (Dependency::any("foo"), Dependency::any("bar"), false),
+            (
+                Dependency::eq("foo", "1.0"),
+                Dependency::eq("foo", "1.0-1"),
+                true,
+            ),
+            (
+                Dependency::eq("foo", "1.0-2"),
+                Dependency::eq("foo", "1.0-1"),
+                false,
+            ),
+            (
+                Dependency::eq("foo", "1.0"),
+                Dependency::eq("foo", "0:1.0"),
+                true,
+            ),
+            (
+                Dependency::eq("foo", "1.0"),
+                Dependency::eq("foo", "1:1.0"),
+                false,
+            ),
+            (
+                Dependency::greater_eq("foo", "1.0"),
+                Dependency::eq("foo", "1.0-1"),
+                true,
+            ),
+            (
+                Dependency::greater("foo", "1.0"),
+                Dependency::eq("foo", "1.0-1"),
+                false,
+            ),
+            (
+                Dependency::greater("foo", "1.0-1"),
+                Dependency::eq("foo", "1.0-2"),
+                true,
+            ),
+            (
+                Dependency::less("foo", "2.0"),
+                Dependency::eq("foo", "1.9"),
+                true,
+            ),
+            (
+                Dependency::less("foo", "2.0"),
+                Dependency::eq("foo", "2.0~rc1"),
+                true,
+            ),
+            (
+                Dependency::less_eq("foo", "2.0"),
+                Dependency::eq("foo", "2.0.1"),
+                false,
+            ),
+            (
+                Dependency::less("foo", "2.0"),
+                Dependency::less("foo", "3.0"),
+                true,
+            ),
+            (
+                Dependency::less("foo", "2.0"),
+                Dependency::greater("foo", "3.0"),
+                false,
+            ),
+            (
+                Dependency::greater("foo", "2.0"),
+                Dependency::less("foo", "3.0"),
+                true,
+            ),
+            (
+                Dependency::less_eq("foo", "2.0"),
+                Dependency::greater_eq("foo", "2.0"),
+                true,
+            ),
+            (
+                Dependency::less("foo", "2.0"),
+                Dependency::greater_eq("foo", "2.0"),
+                false,
+            ),
+        ];
+
+        for (a, b, expected) in test_table {
+            assert_eq!(a.overlaps(&b), expected, "{a:?} vs {b:?}");
+            assert_eq!(b.overlaps(&a), expected, "{b:?} vs {a:?}");
+        }
+    }
 }
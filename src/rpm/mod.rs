@@ -1,22 +1,40 @@
 mod builder;
 mod compressor;
+mod conflicts;
+mod dependencies;
 mod filecaps;
 mod headers;
 mod package;
+mod target;
 mod timestamp;
+mod verification;
+mod version;
 
 pub mod signature;
 
+#[cfg(feature = "repodata")]
+pub mod repodata;
+
 pub use headers::*;
 
 pub use compressor::*;
 
+pub use conflicts::*;
+
+pub use dependencies::*;
+
 pub use package::*;
 
 pub use builder::*;
 
+pub use target::*;
+
 pub use timestamp::*;
 
+pub use verification::*;
+
+pub use version::*;
+
 #[cfg(feature = "chrono")]
 pub use ::chrono;
 
@@ -7,12 +7,20 @@ use std::{
 use digest::Digest;
 use num_traits::FromPrimitive;
 
-use crate::{constants::*, errors::*, CompressionType};
+use crate::{
+    constants::*, decompress_stream, errors::*, CompressionOptions, CompressionType, Compressor,
+    DigestCheck, DigestKind, Evr, Nevra, RpmlibFeature, RpmlibRequirement, Target,
+    VerificationStatus,
+};
 
 #[cfg(feature = "signature-pgp")]
-use crate::signature::pgp::Verifier;
+use crate::signature::pgp::{SignatureInfo, Verifier};
 #[cfg(feature = "signature-meta")]
 use crate::{signature, Timestamp};
+#[cfg(feature = "signature-pgp")]
+use crate::{SignatureCheck, VerificationReport};
+#[cfg(feature = "signature-meta")]
+use base64::Engine;
 #[cfg(feature = "signature-meta")]
 use std::{fmt::Debug, io::Read};
 
@@ -30,10 +38,21 @@ pub struct Digests {
     pub(crate) header_digest_sha256: String,
     /// The sha1 digest of the header.
     pub(crate) header_digest_sha1: String,
+    /// The sha3-256 digest of the header, as used by rpm 6.
+    pub(crate) header_digest_sha3_256: String,
     /// The sha1 digest of the entire header + payload
     pub(crate) header_and_content_digest: Vec<u8>,
 }
 
+/// The signature header tags holding signatures, the ones spanning the header only first.
+const SIGNATURE_TAGS: [IndexSignatureTag; 5] = [
+    IndexSignatureTag::RPMSIGTAG_RSA,
+    IndexSignatureTag::RPMSIGTAG_DSA,
+    IndexSignatureTag::RPMSIGTAG_OPENPGP,
+    IndexSignatureTag::RPMSIGTAG_PGP,
+    IndexSignatureTag::RPMSIGTAG_GPG,
+];
+
 /// A complete rpm file.
 ///
 /// Can either be created using the [`PackageBuilder`](crate::PackageBuilder)
@@ -76,6 +95,119 @@ impl Package {
         self.write(&mut io::BufWriter::new(fs::File::create(path)?))
     }
 
+    /// Determine the compression type of the payload from its magic bytes, falling back to
+    /// `RPMTAG_PAYLOADCOMPRESSOR` if the payload format isn't recognized.
+    ///
+    /// Unlike [`PackageMetadata::get_payload_compressor`], this also works for packages where the
+    /// tag is missing or doesn't match the payload.
+    pub fn detect_payload_compressor(&self) -> Result<CompressionType, Error> {
+        match CompressionType::from_magic(&self.content) {
+            Some(compression) => Ok(compression),
+            None => self.metadata.get_payload_compressor(),
+        }
+    }
+
+    /// Recompress the payload with a different compression type and level.
+    ///
+    /// The payload related header entries, including the payload digest and the `rpmlib()`
+    /// requirement on the compression type, are updated. Since the header changes, the digests
+    /// in the signature header are recomputed and existing signatures are dropped, see
+    /// [`Package::refresh_digests`].
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut package = rpm::Package::open("test_assets/389-ds-base-devel-1.3.8.4-15.el7.x86_64.rpm")?;
+    /// package.recompress(rpm::CompressionWithLevel::Zstd(3))?;
+    /// # Ok(()) }
+    /// ```
+    pub fn recompress(&mut self, compression: impl Into<CompressionOptions>) -> Result<(), Error> {
+        let compression = compression.into();
+
+        let mut decompressor =
+            decompress_stream(self.detect_payload_compressor()?, self.content.as_slice())?;
+        let mut compressor = Compressor::try_from(compression)?;
+        io::copy(&mut decompressor, &mut compressor)?;
+        drop(decompressor);
+        self.content = compressor.finish_compression()?;
+
+        let header = &mut self.metadata.header;
+        match compression.payload_tags() {
+            Some((compressor, flags)) => {
+                header.set_entry(
+                    IndexTag::RPMTAG_PAYLOADCOMPRESSOR,
+                    IndexData::StringTag(compressor.to_owned()),
+                );
+                header.set_entry(IndexTag::RPMTAG_PAYLOADFLAGS, IndexData::StringTag(flags));
+            }
+            None => {
+                for tag in [
+                    IndexTag::RPMTAG_PAYLOADCOMPRESSOR,
+                    IndexTag::RPMTAG_PAYLOADFLAGS,
+                ] {
+                    if header.entry_is_present(tag) {
+                        header.remove_entry(tag)?;
+                    }
+                }
+            }
+        }
+        let payload_digest_algorithm = match self.metadata.get_payload_digest_algorithm() {
+            Err(Error::TagNotFound(_)) => DigestAlgorithm::Sha2_256,
+            algorithm => algorithm?,
+        };
+        let header = &mut self.metadata.header;
+        header.set_entry(
+            IndexTag::RPMTAG_PAYLOADDIGEST,
+            IndexData::StringArray(vec![payload_digest_algorithm.hex_digest(&self.content)]),
+        );
+        header.set_entry(
+            IndexTag::RPMTAG_PAYLOADDIGESTALGO,
+            IndexData::Int32(vec![payload_digest_algorithm as u32]),
+        );
+
+        // only keep the rpmlib() requirement of the new compression type
+        let original_requires = self.metadata.get_requires()?;
+        let mut requires = original_requires.clone();
+        requires.retain(|d| {
+            !matches!(
+                d.name.as_str(),
+                "rpmlib(PayloadIsZstd)"
+                    | "rpmlib(PayloadIsXz)"
+                    | "rpmlib(PayloadIsLzma)"
+                    | "rpmlib(PayloadIsBzip2)"
+            )
+        });
+        if let Some(requirement) = compression.compression_type().rpmlib_requirement() {
+            requires.push(requirement);
+        }
+        if requires != original_requires {
+            let header = &mut self.metadata.header;
+            if requires.is_empty() {
+                for tag in [
+                    IndexTag::RPMTAG_REQUIRENAME,
+                    IndexTag::RPMTAG_REQUIREFLAGS,
+                    IndexTag::RPMTAG_REQUIREVERSION,
+                ] {
+                    header.remove_entry(tag)?;
+                }
+            } else {
+                header.set_entry(
+                    IndexTag::RPMTAG_REQUIRENAME,
+                    IndexData::StringArray(requires.iter().map(|d| d.name.clone()).collect()),
+                );
+                header.set_entry(
+                    IndexTag::RPMTAG_REQUIREFLAGS,
+                    IndexData::Int32(requires.iter().map(|d| d.flags.bits()).collect()),
+                );
+                header.set_entry(
+                    IndexTag::RPMTAG_REQUIREVERSION,
+                    IndexData::StringArray(requires.into_iter().map(|d| d.version).collect()),
+                );
+            }
+        }
+
+        self.refresh_digests()
+    }
+
     /// Prepare both header and content digests as used by the `SignatureIndex`.
     pub(crate) fn create_sig_header_digests(
         header: &[u8],
@@ -91,14 +223,105 @@ impl Package {
 
         let digest_sha1 = hex::encode(sha1::Sha1::digest(header));
         let digest_sha256 = hex::encode(sha2::Sha256::digest(header));
+        let digest_sha3_256 = hex::encode(sha3::Sha3_256::digest(header));
 
         Ok(Digests {
             header_digest_sha256: digest_sha256,
             header_digest_sha1: digest_sha1,
+            header_digest_sha3_256: digest_sha3_256,
             header_and_content_digest: digest_md5,
         })
     }
 
+    /// Recompute the digests in the signature header, after the header was edited with e.g.
+    /// [`PackageMetadata::set_entry`].
+    ///
+    /// Existing signatures no longer match the edited header, so they are dropped. The package
+    /// needs to be signed again if signatures are required. The MD5, SHA1 and SHA256 digests and
+    /// the size are set, the SHA3-256 digest is recomputed if present, and all other entries of
+    /// the signature header, such as the payload size and file signatures, are kept.
+    pub fn refresh_digests(&mut self) -> Result<(), Error> {
+        let mut header = Vec::with_capacity(1024);
+        self.metadata.header.write(&mut header)?;
+        let header_and_content_len = header.len() + self.content.len();
+
+        let Digests {
+            header_digest_sha256,
+            header_digest_sha1,
+            header_digest_sha3_256,
+            header_and_content_digest,
+        } = Self::create_sig_header_digests(header.as_slice(), &self.content)?;
+
+        let signature_header = &mut self.metadata.signature;
+        for tag in SIGNATURE_TAGS.into_iter().chain([
+            IndexSignatureTag::RPMSIGTAG_SIZE,
+            IndexSignatureTag::RPMSIGTAG_LONGSIZE,
+        ]) {
+            if signature_header.entry_is_present(tag) {
+                signature_header.remove_entry(tag)?;
+            }
+        }
+        match u32::try_from(header_and_content_len) {
+            Ok(size) => signature_header.set_entry(
+                IndexSignatureTag::RPMSIGTAG_SIZE,
+                IndexData::Int32(vec![size]),
+            ),
+            Err(_) => signature_header.set_entry(
+                IndexSignatureTag::RPMSIGTAG_LONGSIZE,
+                IndexData::Int64(vec![header_and_content_len as u64]),
+            ),
+        }
+        signature_header.set_entry(
+            IndexSignatureTag::RPMSIGTAG_MD5,
+            IndexData::Bin(header_and_content_digest),
+        );
+        signature_header.set_entry(
+            IndexSignatureTag::RPMSIGTAG_SHA1,
+            IndexData::StringTag(header_digest_sha1),
+        );
+        signature_header.set_entry(
+            IndexSignatureTag::RPMSIGTAG_SHA256,
+            IndexData::StringTag(header_digest_sha256),
+        );
+        if signature_header.entry_is_present(IndexSignatureTag::RPMSIGTAG_SHA3_256) {
+            signature_header.set_entry(
+                IndexSignatureTag::RPMSIGTAG_SHA3_256,
+                IndexData::StringTag(header_digest_sha3_256),
+            );
+        }
+        Ok(())
+    }
+
+    /// Remove all signatures from the package, like `rpmsign --delsign` does.
+    ///
+    /// This strips the `RPMSIGTAG_RSA`, `RPMSIGTAG_DSA`, `RPMSIGTAG_PGP`, `RPMSIGTAG_GPG` and rpm 6
+    /// `RPMSIGTAG_OPENPGP` signatures as well as the file and fsverity signatures from the
+    /// signature header. The digests and size tags are kept, and the header and payload are left
+    /// untouched, so the package can be signed again with another key. It also allows checking
+    /// whether two packages differ only by their signatures.
+    ///
+    /// # Examples
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut package = rpm::Package::open("test_assets/ima_signed.rpm")?;
+    /// package.remove_signatures()?;
+    /// package.verify_digests()?;
+    /// # Ok(()) }
+    /// ```
+    pub fn remove_signatures(&mut self) -> Result<(), Error> {
+        for tag in SIGNATURE_TAGS.into_iter().chain([
+            IndexSignatureTag::RPMSIGTAG_FILESIGNATURES,
+            IndexSignatureTag::RPMSIGTAG_FILESIGNATURE_LENGTH,
+            IndexSignatureTag::RPMSIGTAG_VERITYSIGNATURES,
+            IndexSignatureTag::RPMSIGTAG_VERITYSIGNATUREALGO,
+        ]) {
+            if self.metadata.signature.entry_is_present(tag) {
+                self.metadata.signature.remove_entry(tag)?;
+            }
+        }
+        Ok(())
+    }
+
     /// Create package signatures using an external key and add them to the signature header
     #[cfg(feature = "signature-meta")]
     pub fn sign<S>(&mut self, signer: S) -> Result<(), Error>
@@ -147,6 +370,7 @@ impl Package {
             header_digest_sha256,
             header_digest_sha1,
             header_and_content_digest,
+            ..
         } = Self::create_sig_header_digests(header_bytes.as_slice(), &self.content)?;
 
         let signature_spanning_header_only = signer.sign(header_bytes.as_slice(), t)?;
@@ -178,46 +402,208 @@ impl Package {
         Ok(())
     }
 
-    /// Return the key ids (issuers) of the signature as a hexadecimal string
-    #[cfg(feature = "signature-pgp")]
-    pub fn signature_key_ids(&self) -> Result<Vec<String>, Error> {
-        let mut signature = Err(Error::NoSignatureFound);
-        let rsa_sig = &self
-            .metadata
-            .signature
-            .get_entry_data_as_binary(IndexSignatureTag::RPMSIGTAG_RSA);
-        if let Ok(rsa_sig) = rsa_sig {
-            signature = Verifier::parse_signature(rsa_sig);
-        }
+    /// Add another signature to the package, keeping the existing ones.
+    ///
+    /// See [`add_signature_with_timestamp`][Package::add_signature_with_timestamp] for details.
+    #[cfg(feature = "signature-meta")]
+    pub fn add_signature<S>(&mut self, signer: S) -> Result<(), Error>
+    where
+        S: signature::Signing<Signature = Vec<u8>>,
+    {
+        self.add_signature_with_timestamp(signer, Timestamp::now())
+    }
 
-        let eddsa_sig = &self
-            .metadata
-            .signature
-            .get_entry_data_as_binary(IndexSignatureTag::RPMSIGTAG_DSA);
-        if let Ok(eddsa_sig) = eddsa_sig {
-            signature = Verifier::parse_signature(eddsa_sig);
+    /// Add another signature, created using an external key and provided timestamp, to the
+    /// package while keeping the existing ones. This allows signing a package with several keys,
+    /// e.g. with both the old and the new key during a key rotation.
+    ///
+    /// The signature spans the header only and is stored in the rpm 6 `RPMSIGTAG_OPENPGP` tag,
+    /// along with the SHA3-256 digest of the header. If the package doesn't carry a signature in
+    /// the `RPMSIGTAG_RSA` or `RPMSIGTAG_DSA` tags yet, the signature is stored there as well, so
+    /// older versions of rpm can verify it.
+    ///
+    /// # Examples
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut package = rpm::Package::open("test_assets/ima_signed.rpm")?;
+    /// let raw_secret_key = std::fs::read("./test_assets/secret_key.asc")?;
+    /// let signer = rpm::signature::pgp::Signer::load_from_asc_bytes(&raw_secret_key)?;
+    /// let raw_other_secret_key = std::fs::read("./test_assets/fixture_packages/signing_keys/secret_ed25519.asc")?;
+    /// let other_signer = rpm::signature::pgp::Signer::load_from_asc_bytes(&raw_other_secret_key)?;
+    /// package.sign_with_timestamp(signer, 1_600_000_000)?;
+    /// package.add_signature_with_timestamp(other_signer, 1_600_000_000)?;
+    /// # Ok(()) }
+    /// ```
+    #[cfg(feature = "signature-meta")]
+    pub fn add_signature_with_timestamp<S>(
+        &mut self,
+        signer: S,
+        t: impl TryInto<Timestamp, Error = impl Debug>,
+    ) -> Result<(), Error>
+    where
+        S: signature::Signing<Signature = Vec<u8>>,
+    {
+        let t = t.try_into().unwrap();
+        let mut header_bytes = Vec::<u8>::with_capacity(1024);
+        self.metadata.header.write(&mut header_bytes)?;
+
+        let Digests {
+            header_digest_sha3_256,
+            ..
+        } = Self::create_sig_header_digests(header_bytes.as_slice(), &self.content)?;
+
+        let signature_spanning_header_only = signer.sign(header_bytes.as_slice(), t)?;
+
+        let signature_header = &mut self.metadata.signature;
+        let legacy_tag = match signer.algorithm() {
+            signature::AlgorithmType::RSA => IndexSignatureTag::RPMSIGTAG_RSA,
+            signature::AlgorithmType::EdDSA => IndexSignatureTag::RPMSIGTAG_DSA,
+        };
+        let legacy_signature = [
+            IndexSignatureTag::RPMSIGTAG_RSA,
+            IndexSignatureTag::RPMSIGTAG_DSA,
+        ]
+        .into_iter()
+        .find_map(|tag| signature_header.get_entry_data_as_binary(tag).ok())
+        .map(|signature| signature.to_vec());
+
+        let mut openpgp_signatures = match signature_header
+            .get_entry_data_as_string_array(IndexSignatureTag::RPMSIGTAG_OPENPGP)
+        {
+            Ok(signatures) => signatures.to_vec(),
+            // carry over the existing signature, so the tag holds all of them
+            Err(_) => legacy_signature
+                .iter()
+                .map(|signature| base64::engine::general_purpose::STANDARD.encode(signature))
+                .collect(),
+        };
+        openpgp_signatures.push(
+            base64::engine::general_purpose::STANDARD.encode(&signature_spanning_header_only),
+        );
+
+        if legacy_signature.is_none() {
+            signature_header.set_entry(
+                legacy_tag,
+                IndexData::Bin(signature_spanning_header_only.clone()),
+            );
         }
+        signature_header.set_entry(
+            IndexSignatureTag::RPMSIGTAG_OPENPGP,
+            IndexData::StringArray(openpgp_signatures),
+        );
+        signature_header.set_entry(
+            IndexSignatureTag::RPMSIGTAG_SHA3_256,
+            IndexData::StringTag(header_digest_sha3_256),
+        );
+        Ok(())
+    }
 
-        let rpm_v3_sig = &self
-            .metadata
-            .signature
-            .get_entry_data_as_binary(IndexSignatureTag::RPMSIGTAG_PGP);
-        if let Ok(rpm_v3_sig) = rpm_v3_sig {
-            signature = Verifier::parse_signature(rpm_v3_sig);
+    /// The signatures spanning the header only, from the `RPMSIGTAG_RSA`, `RPMSIGTAG_DSA` and
+    /// rpm 6 `RPMSIGTAG_OPENPGP` tags.
+    #[cfg(feature = "signature-meta")]
+    fn header_signatures(&self) -> Result<Vec<Vec<u8>>, Error> {
+        self.raw_signatures()
+            .into_iter()
+            .filter(|(tag, _)| {
+                !matches!(
+                    tag,
+                    IndexSignatureTag::RPMSIGTAG_PGP | IndexSignatureTag::RPMSIGTAG_GPG
+                )
+            })
+            .map(|(_, signature)| Ok(signature?))
+            .collect()
+    }
+
+    /// The raw signatures of the package, along with the tags they were found in, in the order
+    /// of [`SIGNATURE_TAGS`]. Signatures in `RPMSIGTAG_OPENPGP` are base64 encoded, and fail to
+    /// decode if they are corrupted.
+    ///
+    /// The rpm 6 `RPMSIGTAG_OPENPGP` tag usually holds a copy of the signature in
+    /// `RPMSIGTAG_RSA` or `RPMSIGTAG_DSA`, which is left out.
+    #[cfg(feature = "signature-meta")]
+    fn raw_signatures(&self) -> Vec<(IndexSignatureTag, Result<Vec<u8>, base64::DecodeError>)> {
+        let signature_header = &self.metadata.signature;
+        let mut signatures = Vec::new();
+        for tag in SIGNATURE_TAGS {
+            if tag == IndexSignatureTag::RPMSIGTAG_OPENPGP {
+                let Ok(encoded) = signature_header.get_entry_data_as_string_array(tag) else {
+                    continue;
+                };
+                for signature in encoded {
+                    let signature = base64::engine::general_purpose::STANDARD.decode(signature);
+                    let is_copy = signatures
+                        .iter()
+                        .any(|(_, legacy)| match (legacy, &signature) {
+                            (Ok(legacy), Ok(signature)) => legacy == signature,
+                            _ => false,
+                        });
+                    if !is_copy {
+                        signatures.push((tag, signature));
+                    }
+                }
+            } else if let Ok(signature) = signature_header.get_entry_data_as_binary(tag) {
+                signatures.push((tag, Ok(signature.to_vec())));
+            }
         }
+        signatures
+    }
 
-        let key_ids = signature?
-            .issuer()
-            .iter()
-            .map(|x| format!("{:x}", x))
-            .collect();
+    /// Return the key ids (issuers) of all signatures as hexadecimal strings, without duplicates
+    #[cfg(feature = "signature-pgp")]
+    pub fn signature_key_ids(&self) -> Result<Vec<String>, Error> {
+        let mut key_ids = Vec::new();
+        for (_, signature) in self.raw_signatures() {
+            for key_id in Verifier::parse_signature(&signature?)?.issuer() {
+                let key_id = format!("{:x}", key_id);
+                if !key_ids.contains(&key_id) {
+                    key_ids.push(key_id);
+                }
+            }
+        }
+        if key_ids.is_empty() {
+            return Err(Error::NoSignatureFound);
+        }
         Ok(key_ids)
     }
 
-    // @todo: verify_signature() and verify_digests() don't provide any feedback on whether a signature/digest
-    //        was present and verified or whether it was not present at all.
+    /// Return the details of all OpenPGP signatures of the package, such as the issuer, the
+    /// algorithms used and the creation time, along with the signature header tag each was
+    /// found in.
+    ///
+    /// Signatures in the rpm 6 `RPMSIGTAG_OPENPGP` tag are included as well, except for the copy
+    /// of the signature in `RPMSIGTAG_RSA` or `RPMSIGTAG_DSA` it usually holds. Each signature
+    /// is parsed on its own, so a corrupted signature doesn't hide the others.
+    ///
+    /// # Examples
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use rpm::signature::pgp::HashAlgorithm;
+    ///
+    /// let package = rpm::Package::open("test_assets/ima_signed.rpm")?;
+    /// for signature in package.signatures() {
+    ///     match signature {
+    ///         Ok(signature) if signature.hash_algorithm == HashAlgorithm::SHA1 => {
+    ///             println!("weak signature by {:?}", signature.fingerprint);
+    ///         }
+    ///         Ok(_) => {}
+    ///         Err(e) => println!("unreadable signature: {e}"),
+    ///     }
+    /// }
+    /// # Ok(()) }
+    /// ```
+    #[cfg(feature = "signature-pgp")]
+    pub fn signatures(&self) -> Vec<Result<SignatureInfo, Error>> {
+        self.raw_signatures()
+            .into_iter()
+            .map(|(tag, signature)| SignatureInfo::parse(tag, &signature?))
+            .collect()
+    }
 
-    /// Verify the signature as present within the RPM package.
+    /// Verify the signatures as present within the RPM package.
+    ///
+    /// Packages can carry several signatures, e.g. when they were signed with both an old and a
+    /// new key. Verification succeeds if any of them was made by the key of the verifier, as long
+    /// as none of the signatures made by that key is invalid.
     #[cfg(feature = "signature-meta")]
     pub fn verify_signature<V>(&self, verifier: V) -> Result<(), Error>
     where
@@ -227,27 +613,30 @@ impl Package {
         self.metadata.header.write(&mut header_bytes)?;
         self.verify_digests()?;
 
-        let rsa_sig = &self
-            .metadata
-            .signature
-            .get_entry_data_as_binary(IndexSignatureTag::RPMSIGTAG_RSA);
-        let eddsa_sig = &self
-            .metadata
-            .signature
-            .get_entry_data_as_binary(IndexSignatureTag::RPMSIGTAG_DSA);
+        let header_signatures = self.header_signatures()?;
         let rpm_v3_sig = &self
             .metadata
             .signature
             .get_entry_data_as_binary(IndexSignatureTag::RPMSIGTAG_PGP);
 
-        if !rsa_sig.is_ok() && !eddsa_sig.is_ok() && !rpm_v3_sig.is_ok() {
+        if header_signatures.is_empty() && rpm_v3_sig.is_err() {
             return Err(Error::NoSignatureFound);
         }
 
-        if let Ok(signature_header_only) = eddsa_sig {
-            signature::echo_signature("signature_header(header only)", signature_header_only);
-            verifier.verify(header_bytes.as_slice(), signature_header_only)?;
-        }
+        let mut verified = false;
+        let mut key_not_found = None;
+        let mut check = |result: Result<(), Error>| match result {
+            Ok(()) => {
+                verified = true;
+                Ok(())
+            }
+            // made by another key, which is fine as long as one of the signatures matches
+            Err(err @ Error::KeyNotFoundError { .. }) => {
+                key_not_found = Some(err);
+                Ok(())
+            }
+            Err(err) => Err(err),
+        };
 
         if let Ok(signature_header_and_content) = rpm_v3_sig {
             signature::echo_signature(
@@ -256,19 +645,38 @@ impl Package {
             );
             let header_and_content_cursor =
                 io::Cursor::new(&header_bytes).chain(io::Cursor::new(&self.content));
-            verifier.verify(header_and_content_cursor, signature_header_and_content)?;
+            check(verifier.verify(header_and_content_cursor, signature_header_and_content))?;
         }
 
-        if let Ok(signature_header_only) = rsa_sig {
+        for signature_header_only in &header_signatures {
             signature::echo_signature("signature_header(header only)", signature_header_only);
-            verifier.verify(header_bytes.as_slice(), signature_header_only)?;
+            check(verifier.verify(header_bytes.as_slice(), signature_header_only))?;
         }
 
-        Ok(())
+        if verified {
+            Ok(())
+        } else {
+            Err(key_not_found.unwrap_or(Error::NoSignatureFound))
+        }
     }
 
     /// Verify any digests which may be present in the RPM headers
     pub fn verify_digests(&self) -> Result<(), Error> {
+        let checks = self.check_digests()?;
+        if checks
+            .iter()
+            .any(|check| check.status == VerificationStatus::Bad)
+        {
+            return Err(Error::DigestMismatchError);
+        }
+        Ok(())
+    }
+
+    /// Check each of the digests a package can carry, reporting whether it is present and
+    /// whether it matches the package.
+    ///
+    /// Fails if the payload digest uses an unknown algorithm.
+    pub fn check_digests(&self) -> Result<Vec<DigestCheck>, Error> {
         let mut header = Vec::<u8>::with_capacity(1024);
         // make sure to not hash any previous signatures in the header
         self.metadata.header.write(&mut header)?;
@@ -276,72 +684,161 @@ impl Package {
         let pkg_actual_digests =
             Self::create_sig_header_digests(header.as_slice(), self.content.as_slice())?;
 
-        let md5 = self
-            .metadata
-            .signature
-            .get_entry_data_as_binary(IndexSignatureTag::RPMSIGTAG_MD5);
-        let sha1 = self
-            .metadata
-            .signature
-            .get_entry_data_as_string(IndexSignatureTag::RPMSIGTAG_SHA1);
-        let sha256 = self
-            .metadata
-            .signature
-            .get_entry_data_as_string(IndexSignatureTag::RPMSIGTAG_SHA256);
-
-        if let Ok(md5) = md5 {
-            if md5 != pkg_actual_digests.header_and_content_digest {
-                return Err(Error::DigestMismatchError);
-            }
-        }
-
-        if let Ok(sha1) = sha1 {
-            if sha1 != pkg_actual_digests.header_digest_sha1 {
-                return Err(Error::DigestMismatchError);
-            }
-        }
-
-        if let Ok(sha256) = sha256 {
-            if sha256 != pkg_actual_digests.header_digest_sha256 {
-                return Err(Error::DigestMismatchError);
-            }
-        }
+        let status = |expected: Result<&[u8], Error>, actual: &[u8]| match expected {
+            Ok(expected) if expected == actual => VerificationStatus::Verified,
+            Ok(_) => VerificationStatus::Bad,
+            Err(_) => VerificationStatus::NotPresent,
+        };
+        let signature = &self.metadata.signature;
+        let string_entry = |tag| {
+            signature
+                .get_entry_data_as_string(tag)
+                .map(|digest| digest.as_bytes())
+        };
 
         let payload_digest_val = self
             .metadata
             .header
             .get_entry_data_as_string_array(IndexTag::RPMTAG_PAYLOADDIGEST);
-        let payload_digest_algo = self
-            .metadata
-            .header
-            .get_entry_data_as_u32(IndexTag::RPMTAG_PAYLOADDIGESTALGO);
+        let payload_digest_algo = match self.metadata.get_payload_digest_algorithm() {
+            Err(Error::TagNotFound(_)) => None,
+            algorithm => Some(algorithm?),
+        };
+        let payload_status = match (payload_digest_val, payload_digest_algo) {
+            (Ok(payload_digest_val), Some(payload_digest_algo)) => {
+                let payload_digest = payload_digest_algo.hex_digest(&self.content);
+                status(
+                    payload_digest_val
+                        .first()
+                        .map(|digest| digest.as_bytes())
+                        .ok_or_else(|| {
+                            Error::TagNotFound(IndexTag::RPMTAG_PAYLOADDIGEST.to_string())
+                        }),
+                    payload_digest.as_bytes(),
+                )
+            }
+            _ => VerificationStatus::NotPresent,
+        };
 
-        if let (Ok(payload_digest_val), Ok(payload_digest_algo)) =
-            (payload_digest_val, payload_digest_algo)
-        {
-            let payload_digest_algo = DigestAlgorithm::from_u32(payload_digest_algo)
-                .expect("Completely unknown payload digest algorithm");
-
-            // @todo: UnsupportedDigestAlgorithm is awkward, if a number is outside the range of the expected
-            // variants to begin with, we can't even return it, as it carries a DigestAlgorithm. But also, in
-            // this case even when it is "supported" by the library in general it is not supported here in particular
-            // Not that that should happen here for a while to come.
-
-            let mut hasher = match payload_digest_algo {
-                DigestAlgorithm::Sha2_256 => sha2::Sha256::default(),
-                a => return Err(Error::UnsupportedDigestAlgorithm(a)),
-                // At the present moment even rpmbuild only supports sha256
-            };
-            let payload_digest = {
-                hasher.update(self.content.as_slice());
-                hex::encode(hasher.finalize())
-            };
-            if payload_digest != payload_digest_val[0] {
-                return Err(Error::DigestMismatchError);
+        Ok(vec![
+            DigestCheck {
+                kind: DigestKind::HeaderSha3_256,
+                status: status(
+                    string_entry(IndexSignatureTag::RPMSIGTAG_SHA3_256),
+                    pkg_actual_digests.header_digest_sha3_256.as_bytes(),
+                ),
+            },
+            DigestCheck {
+                kind: DigestKind::HeaderSha256,
+                status: status(
+                    string_entry(IndexSignatureTag::RPMSIGTAG_SHA256),
+                    pkg_actual_digests.header_digest_sha256.as_bytes(),
+                ),
+            },
+            DigestCheck {
+                kind: DigestKind::HeaderSha1,
+                status: status(
+                    string_entry(IndexSignatureTag::RPMSIGTAG_SHA1),
+                    pkg_actual_digests.header_digest_sha1.as_bytes(),
+                ),
+            },
+            DigestCheck {
+                kind: DigestKind::Payload(payload_digest_algo),
+                status: payload_status,
+            },
+            DigestCheck {
+                kind: DigestKind::Md5,
+                status: status(
+                    signature.get_entry_data_as_binary(IndexSignatureTag::RPMSIGTAG_MD5),
+                    &pkg_actual_digests.header_and_content_digest,
+                ),
+            },
+        ])
+    }
+
+    /// Check all digests and signatures of the package, reporting for each whether it is present
+    /// and whether it verified, along with the details of the signatures. This mirrors
+    /// `rpmkeys -Kv`.
+    ///
+    /// Signatures made by keys the verifier doesn't have are reported as
+    /// [`VerificationStatus::NoKey`] rather than failing the whole check.
+    ///
+    /// # Examples
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let package = rpm::Package::open("test_assets/ima_signed.rpm")?;
+    /// let raw_pub_key = std::fs::read("test_assets/public_key.asc")?;
+    /// let verifier = rpm::signature::pgp::Verifier::load_from_asc_bytes(&raw_pub_key)?;
+    /// let report = package.verification_report(&verifier)?;
+    /// if !report.is_signed() {
+    ///     eprintln!("package is unsigned");
+    /// }
+    /// print!("{report}");
+    /// # Ok(()) }
+    /// ```
+    #[cfg(feature = "signature-pgp")]
+    pub fn verification_report<V>(&self, verifier: V) -> Result<VerificationReport, Error>
+    where
+        V: signature::Verifying<Signature = Vec<u8>>,
+    {
+        let mut header_bytes = Vec::<u8>::with_capacity(1024);
+        self.metadata.header.write(&mut header_bytes)?;
+        let header_and_content =
+            || io::Cursor::new(&header_bytes).chain(io::Cursor::new(&self.content));
+
+        let raw_signatures = self.raw_signatures();
+        let mut signatures = Vec::new();
+        for tag in SIGNATURE_TAGS {
+            let header_only = !matches!(
+                tag,
+                IndexSignatureTag::RPMSIGTAG_PGP | IndexSignatureTag::RPMSIGTAG_GPG
+            );
+            for (_, signature) in raw_signatures.iter().filter(|(t, _)| *t == tag) {
+                signatures.push(match signature {
+                    Ok(signature) if header_only => {
+                        Self::check_signature(&verifier, tag, signature, header_bytes.as_slice())
+                    }
+                    Ok(signature) => {
+                        Self::check_signature(&verifier, tag, signature, header_and_content())
+                    }
+                    Err(_) => SignatureCheck {
+                        tag,
+                        info: None,
+                        status: VerificationStatus::Bad,
+                    },
+                });
+            }
+            if !self.metadata.signature.entry_is_present(tag) {
+                signatures.push(SignatureCheck::not_present(tag));
             }
         }
 
-        Ok(())
+        Ok(VerificationReport {
+            signatures,
+            digests: self.check_digests()?,
+        })
+    }
+
+    #[cfg(feature = "signature-pgp")]
+    fn check_signature<V>(
+        verifier: &V,
+        tag: IndexSignatureTag,
+        signature: &[u8],
+        data: impl io::Read,
+    ) -> SignatureCheck
+    where
+        V: signature::Verifying<Signature = Vec<u8>>,
+    {
+        let status = match verifier.verify(data, signature) {
+            Ok(()) => VerificationStatus::Verified,
+            Err(Error::KeyNotFoundError { .. }) => VerificationStatus::NoKey,
+            Err(_) => VerificationStatus::Bad,
+        };
+        SignatureCheck {
+            tag,
+            info: SignatureInfo::parse(tag, signature).ok(),
+            status,
+        }
     }
 }
 
@@ -383,6 +880,61 @@ impl PackageMetadata {
         Ok(())
     }
 
+    /// Set the value of a header entry, adding the entry if it isn't present yet.
+    ///
+    /// The header is regenerated, and its digests and signatures become invalid - use
+    /// [`Package::refresh_digests`] once all edits are done.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use rpm::{IndexTag, TagValue};
+    ///
+    /// let mut package = rpm::Package::open("test_assets/ima_signed.rpm")?;
+    /// package.metadata.set_entry(IndexTag::RPMTAG_RELEASE, "2")?;
+    /// package.metadata.set_entry(IndexTag::RPMTAG_VENDOR, "Example Corp")?;
+    /// package.refresh_digests()?;
+    /// # Ok(()) }
+    /// ```
+    pub fn set_entry(&mut self, tag: IndexTag, value: impl Into<TagValue>) -> Result<(), Error> {
+        Self::check_editable(tag)?;
+        self.header.set_entry(tag, value.into().into());
+        Ok(())
+    }
+
+    /// Replace the value of a header entry, which must already be present.
+    ///
+    /// The same caveats as for [`PackageMetadata::set_entry`] apply.
+    pub fn replace_entry(
+        &mut self,
+        tag: IndexTag,
+        value: impl Into<TagValue>,
+    ) -> Result<(), Error> {
+        Self::check_editable(tag)?;
+        if !self.header.entry_is_present(tag) {
+            return Err(Error::TagNotFound(tag.to_string()));
+        }
+        self.header.set_entry(tag, value.into().into());
+        Ok(())
+    }
+
+    /// Remove a header entry.
+    ///
+    /// The same caveats as for [`PackageMetadata::set_entry`] apply.
+    pub fn remove_entry(&mut self, tag: IndexTag) -> Result<(), Error> {
+        Self::check_editable(tag)?;
+        self.header.remove_entry(tag)
+    }
+
+    fn check_editable(tag: IndexTag) -> Result<(), Error> {
+        match tag {
+            IndexTag::RPMTAG_HEADERIMAGE
+            | IndexTag::RPMTAG_HEADERSIGNATURES
+            | IndexTag::RPMTAG_HEADERIMMUTABLE
+            | IndexTag::RPMTAG_HEADERREGIONS => Err(Error::UneditableTag(tag.to_string())),
+            _ => Ok(()),
+        }
+    }
+
     /// Whether this package is a source package, or not
     #[inline]
     pub fn is_source_package(&self) -> bool {
@@ -415,6 +967,52 @@ impl PackageMetadata {
             .get_entry_data_as_string(IndexTag::RPMTAG_RELEASE)
     }
 
+    /// Get the package epoch, version and release combined, for comparing against other packages
+    pub fn get_evr(&self) -> Result<Evr, Error> {
+        let epoch = match self.get_epoch() {
+            Ok(epoch) => Some(epoch),
+            Err(Error::TagNotFound(_)) => None,
+            Err(e) => return Err(e),
+        };
+        Ok(Evr {
+            epoch,
+            version: self.get_version()?.to_owned(),
+            release: Some(self.get_release()?.to_owned()),
+        })
+    }
+
+    /// Get the package name, epoch, version, release and architecture
+    ///
+    /// The architecture of source packages is reported as `src`, or `nosrc` if sources or patches
+    /// were left out of the package, as in their file name.
+    pub fn get_nevra(&self) -> Result<Nevra, Error> {
+        let evr = self.get_evr()?;
+        let arch = if self.is_source_package() {
+            // source packages with sources or patches left out are `nosrc`
+            if self.header.entry_is_present(IndexTag::RPMTAG_NOSOURCE)
+                || self.header.entry_is_present(IndexTag::RPMTAG_NOPATCH)
+            {
+                "nosrc"
+            } else {
+                "src"
+            }
+        } else {
+            self.get_arch()?
+        };
+        Ok(Nevra {
+            name: self.get_name()?.to_owned(),
+            epoch: evr.epoch,
+            version: evr.version,
+            release: evr.release.unwrap_or_default(),
+            arch: arch.to_owned(),
+        })
+    }
+
+    /// Get the canonical file name of the package, `name-version-release.arch.rpm`
+    pub fn get_canonical_filename(&self) -> Result<String, Error> {
+        Ok(self.get_nevra()?.filename())
+    }
+
     /// Get the package architecture
     #[inline]
     pub fn get_arch(&self) -> Result<&str, Error> {
@@ -506,6 +1104,224 @@ impl PackageMetadata {
             .get_entry_data_as_string(IndexTag::RPMTAG_SOURCERPM)
     }
 
+    /// Get the URL the package can be found at in the distribution. This is commonly not present.
+    #[inline]
+    pub fn get_dist_url(&self) -> Result<&str, Error> {
+        self.header
+            .get_entry_data_as_string(IndexTag::RPMTAG_DISTURL)
+    }
+
+    /// Get the name of the distribution this package is part of
+    #[inline]
+    pub fn get_distribution(&self) -> Result<&str, Error> {
+        self.header
+            .get_entry_data_as_string(IndexTag::RPMTAG_DISTRIBUTION)
+    }
+
+    /// Get the distribution tag, an identifier of the distribution release
+    #[inline]
+    pub fn get_disttag(&self) -> Result<&str, Error> {
+        self.header
+            .get_entry_data_as_string(IndexTag::RPMTAG_DISTTAG)
+    }
+
+    /// Get the URL bugs of the package should be reported to
+    #[inline]
+    pub fn get_bug_url(&self) -> Result<&str, Error> {
+        self.header
+            .get_entry_data_as_string(IndexTag::RPMTAG_BUGURL)
+    }
+
+    /// Get the platform the package was built for, e.g. `x86_64-redhat-linux-gnu`
+    #[inline]
+    pub fn get_platform(&self) -> Result<&str, Error> {
+        self.header
+            .get_entry_data_as_string(IndexTag::RPMTAG_PLATFORM)
+    }
+
+    /// Get the compiler flags the packaged software was built with
+    #[inline]
+    pub fn get_optflags(&self) -> Result<&str, Error> {
+        self.header
+            .get_entry_data_as_string(IndexTag::RPMTAG_OPTFLAGS)
+    }
+
+    /// Get the modularity label of packages which are part of a module stream
+    #[inline]
+    pub fn get_modularity_label(&self) -> Result<&str, Error> {
+        self.header
+            .get_entry_data_as_string(IndexTag::RPMTAG_MODULARITYLABEL)
+    }
+
+    /// Get the license of the source package, if it differs from the license of this package
+    #[inline]
+    pub fn get_source_license(&self) -> Result<&str, Error> {
+        self.header
+            .get_entry_data_as_string(IndexTag::RPMTAG_SOURCELICENSE)
+    }
+
+    /// Get the name, epoch, version, release and architecture of the source RPM package used to
+    /// build this package, parsed from [`PackageMetadata::get_source_rpm`]
+    pub fn get_source_nevra(&self) -> Result<Nevra, Error> {
+        Nevra::from_filename(self.get_source_rpm()?)
+    }
+
+    /// Get the sources (`SourceN`) of a source package
+    ///
+    /// rpm doesn't record the number of each source, and `rpmbuild` doesn't list them in the
+    /// order of their numbers, so [`SourceFile::number`] is only known for an excluded source
+    /// if it is the only one. Whether a source is excluded is determined from the files of the
+    /// package, the numbers of the excluded sources are returned by
+    /// [`PackageMetadata::get_nosource_numbers`].
+    pub fn get_sources(&self) -> Result<Vec<SourceFile>, Error> {
+        self.get_source_files(IndexTag::RPMTAG_SOURCE, self.get_nosource_numbers()?)
+    }
+
+    /// Get the patches (`PatchN`) of a source package
+    ///
+    /// The same caveats as for [`PackageMetadata::get_sources`] apply, the numbers of the
+    /// excluded patches are returned by [`PackageMetadata::get_nopatch_numbers`].
+    pub fn get_patches(&self) -> Result<Vec<SourceFile>, Error> {
+        self.get_source_files(IndexTag::RPMTAG_PATCH, self.get_nopatch_numbers()?)
+    }
+
+    /// Get the numbers `N` of the sources left out of a source package (`NoSourceN`)
+    pub fn get_nosource_numbers(&self) -> Result<Vec<u32>, Error> {
+        match self
+            .header
+            .get_entry_data_as_u32_array(IndexTag::RPMTAG_NOSOURCE)
+        {
+            Err(Error::TagNotFound(_)) => Ok(vec![]),
+            numbers => numbers,
+        }
+    }
+
+    /// Get the numbers `N` of the patches left out of a source package (`NoPatchN`)
+    pub fn get_nopatch_numbers(&self) -> Result<Vec<u32>, Error> {
+        match self
+            .header
+            .get_entry_data_as_u32_array(IndexTag::RPMTAG_NOPATCH)
+        {
+            Err(Error::TagNotFound(_)) => Ok(vec![]),
+            numbers => numbers,
+        }
+    }
+
+    fn get_source_files(
+        &self,
+        names_tag: IndexTag,
+        excluded_numbers: Vec<u32>,
+    ) -> Result<Vec<SourceFile>, Error> {
+        let names = match self.header.get_entry_data_as_string_array(names_tag) {
+            Ok(names) => names,
+            Err(Error::TagNotFound(_)) => return Ok(vec![]),
+            Err(e) => return Err(e),
+        };
+        // the files of source packages are at the top level, so a source which isn't among them
+        // was left out
+        let files = self.get_file_paths()?;
+        let mut sources: Vec<SourceFile> = names
+            .iter()
+            .map(|name| SourceFile {
+                name: name.to_owned(),
+                number: None,
+                excluded: !files.iter().any(|file| file.as_os_str() == name.as_str()),
+            })
+            .collect();
+        if let [number] = excluded_numbers[..] {
+            let mut excluded = sources.iter_mut().filter(|source| source.excluded);
+            if let (Some(source), None) = (excluded.next(), excluded.next()) {
+                source.number = Some(number);
+            }
+        }
+        Ok(sources)
+    }
+
+    /// Get the build dependencies (`BuildRequires`) of a source package
+    ///
+    /// These are stored as the "requires" of the source package, the `rpmlib()` dependencies are
+    /// left out. Binary packages have no build dependencies.
+    pub fn get_build_requires(&self) -> Result<Vec<Dependency>, Error> {
+        if !self.is_source_package() {
+            return Ok(vec![]);
+        }
+        let mut requires = self.get_requires()?;
+        requires.retain(|d| !d.flags.contains(DependencyFlags::RPMLIB));
+        Ok(requires)
+    }
+
+    /// Get the `rpmlib()` requirements of the package, which describe the rpm features needed to
+    /// install it, along with the rpm release which added each feature.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let metadata = rpm::PackageMetadata::open("test_assets/389-ds-base-devel-1.3.8.4-15.el7.x86_64.rpm")?;
+    /// for requirement in metadata.get_rpmlib_requirements()? {
+    ///     match requirement.feature {
+    ///         Some(feature) => println!("{} (rpm {})", requirement.dependency, feature.introduced_in),
+    ///         None => println!("{} (unknown)", requirement.dependency),
+    ///     }
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn get_rpmlib_requirements(&self) -> Result<Vec<RpmlibRequirement>, Error> {
+        Ok(self
+            .get_requires()?
+            .into_iter()
+            .filter(|d| d.flags.contains(DependencyFlags::RPMLIB))
+            .map(|dependency| RpmlibRequirement {
+                feature: RpmlibFeature::from_dependency(&dependency),
+                dependency,
+            })
+            .collect())
+    }
+
+    /// Get the `rpmlib()` requirements which the rpm of the target doesn't satisfy. If there are
+    /// none, the target's rpm can install the package, as far as rpm features are concerned.
+    ///
+    /// Requirements on features which aren't known to this library are assumed to be unsupported.
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let metadata = rpm::PackageMetadata::open("test_assets/389-ds-base-devel-1.3.8.4-15.el7.x86_64.rpm")?;
+    /// assert!(metadata.get_unsupported_rpmlib_requirements(rpm::Target::El7)?.is_empty());
+    /// # Ok(()) }
+    /// ```
+    pub fn get_unsupported_rpmlib_requirements(
+        &self,
+        target: Target,
+    ) -> Result<Vec<RpmlibRequirement>, Error> {
+        let mut requirements = self.get_rpmlib_requirements()?;
+        requirements.retain(|requirement| !requirement.is_supported_by(target));
+        Ok(requirements)
+    }
+
+    /// Get the build conflicts (`BuildConflicts`) of a source package
+    ///
+    /// These are stored as the "conflicts" of the source package. Binary packages have no build
+    /// conflicts.
+    pub fn get_build_conflicts(&self) -> Result<Vec<Dependency>, Error> {
+        if !self.is_source_package() {
+            return Ok(vec![]);
+        }
+        self.get_conflicts()
+    }
+
+    /// Get the path of the spec file contained in a source package, if any
+    pub fn get_spec_file_path(&self) -> Result<Option<PathBuf>, Error> {
+        Ok(self
+            .get_file_entries()?
+            .into_iter()
+            .find(|f| f.flags.contains(FileFlags::SPECFILE))
+            .map(|f| f.path))
+    }
+
+    /// Get the content of the spec file a source package was built from
+    #[inline]
+    pub fn get_spec(&self) -> Result<&str, Error> {
+        self.header.get_entry_data_as_string(IndexTag::RPMTAG_SPEC)
+    }
+
     /// Get the %pre scriptlet for this package
     #[inline]
     pub fn get_pre_install_script(&self) -> Result<Scriptlet, Error> {
@@ -554,6 +1370,12 @@ impl PackageMetadata {
         self.get_scriptlet(POSTUNTRANS_TAGS)
     }
 
+    /// Get the %verifyscript scriptlet for this package
+    #[inline]
+    pub fn get_verify_script(&self) -> Result<Scriptlet, Error> {
+        self.get_scriptlet(VERIFYSCRIPT_TAGS)
+    }
+
     fn get_dependencies(
         &self,
         names_tag: IndexTag,
@@ -699,6 +1521,18 @@ impl PackageMetadata {
         )
     }
 
+    /// Get a list of ordering hints (`OrderWithRequires`) of this package
+    ///
+    /// These only affect the order in which "rpm" installs packages within a transaction, as if
+    /// they were a "requires", but unlike a "requires" they don't need to be satisfied.
+    pub fn get_order_with_requires(&self) -> Result<Vec<Dependency>, Error> {
+        self.get_dependencies(
+            IndexTag::RPMTAG_ORDERNAME,
+            IndexTag::RPMTAG_ORDERFLAGS,
+            IndexTag::RPMTAG_ORDERVERSION,
+        )
+    }
+
     /// Get a list of reverse-dependencies which this package "supplements"
     ///
     /// "rpm" itself will ignore such dependencies, but a dependency solver may elect to treat this
@@ -789,6 +1623,24 @@ impl PackageMetadata {
             )
     }
 
+    /// Get the compression type and settings of the payload, parsed from `RPMTAG_PAYLOADFLAGS`,
+    /// e.g. `19T8L` for zstd level 19 using 8 threads and long distance matching.
+    ///
+    /// Settings which aren't recorded in the header take their default values. The result can
+    /// be passed to [`crate::PackageBuilder::compression`] to reproduce the compression of the
+    /// package.
+    pub fn get_payload_compression(&self) -> Result<CompressionOptions, Error> {
+        let compression = self.get_payload_compressor()?;
+        match self
+            .header
+            .get_entry_data_as_string(IndexTag::RPMTAG_PAYLOADFLAGS)
+        {
+            Ok(flags) => CompressionOptions::from_payload_flags(compression, flags),
+            Err(Error::TagNotFound(_)) => Ok(compression.into()),
+            Err(e) => Err(e),
+        }
+    }
+
     /// Extract a the set of contained file names.
     pub fn get_file_paths(&self) -> Result<Vec<PathBuf>, Error> {
         // reconstruct the messy de-constructed paths
@@ -857,6 +1709,18 @@ impl PackageMetadata {
             })
     }
 
+    /// The digest algorithm used for the payload digest.
+    pub fn get_payload_digest_algorithm(&self) -> Result<DigestAlgorithm, Error> {
+        self.header
+            .get_entry_data_as_u32(IndexTag::RPMTAG_PAYLOADDIGESTALGO)
+            .and_then(|x| {
+                DigestAlgorithm::from_u32(x).ok_or_else(|| Error::InvalidTagValueEnumVariant {
+                    tag: IndexTag::RPMTAG_PAYLOADDIGESTALGO.to_string(),
+                    variant: x,
+                })
+            })
+    }
+
     /// Extract a the set of contained file names including the additional metadata.
     pub fn get_file_entries(&self) -> Result<Vec<FileEntry>, Error> {
         // rpm does not encode it, if it is the default md5
@@ -0,0 +1,578 @@
+//! Generation and parsing of yum/dnf repository metadata (`repodata/`), compatible with
+//! `createrepo_c`.
+//!
+//! ```no_run
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use rpm::repodata::RepodataBuilder;
+//!
+//! // index all packages below the repository directory, like `createrepo_c /srv/repo`
+//! RepodataBuilder::from_directory("/srv/repo")?
+//!     .compression(rpm::CompressionType::Zstd)
+//!     .write("/srv/repo")?;
+//!
+//! // compare a package against the published one
+//! let published = rpm::repodata::Repodata::open("/srv/repo")?;
+//! let package = rpm::PackageMetadata::open("foo-1.1-1.x86_64.rpm")?;
+//! for record in published.packages.iter().filter(|r| r.name == package.get_name().unwrap()) {
+//!     assert!(package.get_evr()? > record.evr());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use digest::Digest;
+
+use crate::{
+    errors::*, ChangelogEntry, CompressionWithLevel, Dependency, Evr, FileFlags, FileMode,
+    IndexSignatureTag, Nevra, Package, PackageMetadata,
+};
+
+mod reader;
+mod writer;
+
+/// The number of changelog entries per package included in `other.xml` by default, the same as
+/// `createrepo_c`.
+pub const DEFAULT_CHANGELOG_LIMIT: usize = 10;
+
+/// The type of a file listed in the repository metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RepoFileType {
+    File,
+    Dir,
+    Ghost,
+}
+
+/// A file listed in the repository metadata.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RepoFile {
+    pub path: String,
+    pub file_type: RepoFileType,
+}
+
+/// All the information about a single package contained in the repository metadata.
+///
+/// Optional strings which are not set by the package (e.g. the vendor) are empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageRecord {
+    pub name: String,
+    pub arch: String,
+    pub epoch: u32,
+    pub version: String,
+    pub release: String,
+    /// The type of [`PackageRecord::checksum`], e.g. `sha256`
+    pub checksum_type: String,
+    /// The hex encoded checksum of the whole package file, which also serves as the package id
+    pub checksum: String,
+    pub summary: String,
+    pub description: String,
+    pub packager: String,
+    pub url: String,
+    /// Modification time of the package file
+    pub file_time: u64,
+    pub build_time: u64,
+    /// Size of the package file
+    pub package_size: u64,
+    /// Size of the files once installed
+    pub installed_size: u64,
+    /// Size of the uncompressed payload
+    pub archive_size: u64,
+    /// Path of the package, relative to the repository root
+    pub location_href: String,
+    pub license: String,
+    pub vendor: String,
+    pub group: String,
+    pub build_host: String,
+    pub source_rpm: String,
+    /// The start of the header and the end of the header, i.e. the start of the payload
+    pub header_range: (u64, u64),
+    pub provides: Vec<Dependency>,
+    pub requires: Vec<Dependency>,
+    pub conflicts: Vec<Dependency>,
+    pub obsoletes: Vec<Dependency>,
+    pub recommends: Vec<Dependency>,
+    pub suggests: Vec<Dependency>,
+    pub enhances: Vec<Dependency>,
+    pub supplements: Vec<Dependency>,
+    pub files: Vec<RepoFile>,
+    /// Changelog entries, newest first
+    pub changelogs: Vec<ChangelogEntry>,
+}
+
+impl PackageRecord {
+    /// The epoch, version and release of the package.
+    pub fn evr(&self) -> Evr {
+        Evr::new(self.epoch, self.version.clone(), self.release.clone())
+    }
+
+    /// The name, epoch, version, release and architecture of the package.
+    pub fn nevra(&self) -> Nevra {
+        Nevra::new(
+            self.name.clone(),
+            Some(self.epoch),
+            self.version.clone(),
+            self.release.clone(),
+            self.arch.clone(),
+        )
+    }
+
+    /// Create the record of a package which will be located at `location_href`, relative to the
+    /// repository root.
+    ///
+    /// As the package isn't read from a file, its build time is used as the file time.
+    pub fn from_package(
+        package: &Package,
+        location_href: impl Into<String>,
+    ) -> Result<Self, Error> {
+        let mut hasher = sha2::Sha256::default();
+        package.write(&mut hasher)?;
+        let package_size = (package.metadata.get_package_segment_offsets().payload as usize
+            + package.content.len()) as u64;
+        let build_time = package.metadata.get_build_time().unwrap_or_default();
+
+        Self::from_metadata(
+            &package.metadata,
+            hex::encode(hasher.finalize()),
+            package_size,
+            build_time,
+            location_href.into(),
+        )
+    }
+
+    /// Create the record of a package file which will be located at `location_href`, relative
+    /// to the repository root.
+    pub fn from_package_file(
+        path: impl AsRef<Path>,
+        location_href: impl Into<String>,
+    ) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let metadata = PackageMetadata::open(path)?;
+
+        let mut file = fs::File::open(path)?;
+        let mut hasher = sha2::Sha256::default();
+        io::copy(&mut file, &mut hasher)?;
+        let file_metadata = file.metadata()?;
+        let file_time = modification_time(&file_metadata)?;
+
+        Self::from_metadata(
+            &metadata,
+            hex::encode(hasher.finalize()),
+            file_metadata.len(),
+            file_time,
+            location_href.into(),
+        )
+    }
+
+    fn from_metadata(
+        metadata: &PackageMetadata,
+        checksum: String,
+        package_size: u64,
+        file_time: u64,
+        location_href: String,
+    ) -> Result<Self, Error> {
+        let optional = |value: Result<&str, Error>| match value {
+            Ok(value) => Ok(value.to_owned()),
+            Err(Error::TagNotFound(_)) => Ok(String::new()),
+            Err(e) => Err(e),
+        };
+
+        let offsets = metadata.get_package_segment_offsets();
+        let archive_size = metadata
+            .signature
+            .get_entry_data_as_u64(IndexSignatureTag::RPMSIGTAG_LONGARCHIVESIZE)
+            .or_else(|_| {
+                metadata
+                    .signature
+                    .get_entry_data_as_u32(IndexSignatureTag::RPMSIGTAG_PAYLOADSIZE)
+                    .map(u64::from)
+            })
+            .unwrap_or_default();
+
+        let files: Vec<RepoFile> = metadata
+            .get_file_entries()?
+            .into_iter()
+            .map(|entry| RepoFile {
+                path: entry.path.to_string_lossy().into_owned(),
+                file_type: if entry.flags.contains(FileFlags::GHOST) {
+                    RepoFileType::Ghost
+                } else if matches!(entry.mode, FileMode::Dir { .. }) {
+                    RepoFileType::Dir
+                } else {
+                    RepoFileType::File
+                },
+            })
+            .collect();
+
+        // like createrepo_c, leave out requirements which are only relevant to rpm itself or
+        // which the package satisfies on its own
+        let provides = metadata.get_provides()?;
+        let mut requires: Vec<Dependency> = Vec::new();
+        for requirement in metadata.get_requires()? {
+            if requirement.name.starts_with("rpmlib(")
+                || provides.contains(&requirement)
+                || files.iter().any(|f| f.path == requirement.name)
+                || requires.contains(&requirement)
+            {
+                continue;
+            }
+            requires.push(requirement);
+        }
+
+        Ok(PackageRecord {
+            name: metadata.get_name()?.to_owned(),
+            arch: if metadata.is_source_package() {
+                "src".to_owned()
+            } else {
+                metadata.get_arch()?.to_owned()
+            },
+            epoch: metadata.get_epoch().unwrap_or(0),
+            version: metadata.get_version()?.to_owned(),
+            release: metadata.get_release()?.to_owned(),
+            checksum_type: "sha256".to_owned(),
+            checksum,
+            summary: optional(metadata.get_summary())?,
+            description: optional(metadata.get_description())?,
+            packager: optional(metadata.get_packager())?,
+            url: optional(metadata.get_url())?,
+            file_time,
+            build_time: metadata.get_build_time().unwrap_or_default(),
+            package_size,
+            installed_size: metadata.get_installed_size().unwrap_or_default(),
+            archive_size,
+            location_href,
+            license: optional(metadata.get_license())?,
+            vendor: optional(metadata.get_vendor())?,
+            group: optional(metadata.get_group())?,
+            build_host: optional(metadata.get_build_host())?,
+            source_rpm: optional(metadata.get_source_rpm())?,
+            header_range: (offsets.header, offsets.payload),
+            provides,
+            requires,
+            conflicts: metadata.get_conflicts()?,
+            obsoletes: metadata.get_obsoletes()?,
+            recommends: metadata.get_recommends()?,
+            suggests: metadata.get_suggests()?,
+            enhances: metadata.get_enhances()?,
+            supplements: metadata.get_supplements()?,
+            files,
+            changelogs: metadata.get_changelog_entries()?,
+        })
+    }
+}
+
+/// A metadata file referenced by `repomd.xml`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepomdData {
+    /// The type of metadata, e.g. `primary`
+    pub data_type: String,
+    /// Path of the file, relative to the repository root
+    pub location_href: String,
+    /// The checksum type (e.g. `sha256`) and hex encoded checksum of the file
+    pub checksum: (String, String),
+    /// The checksum type and checksum of the uncompressed file
+    pub open_checksum: Option<(String, String)>,
+    pub timestamp: Option<u64>,
+    pub size: Option<u64>,
+    /// Size of the uncompressed file
+    pub open_size: Option<u64>,
+}
+
+/// The contents of `repomd.xml`, the index of all metadata files of a repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repomd {
+    pub revision: String,
+    pub data: Vec<RepomdData>,
+}
+
+impl Repomd {
+    /// Read `repodata/repomd.xml` of a repository.
+    pub fn open(repo_dir: impl AsRef<Path>) -> Result<Self, Error> {
+        let file = fs::File::open(repo_dir.as_ref().join("repodata/repomd.xml"))?;
+        Self::parse(io::BufReader::new(file))
+    }
+
+    /// Parse the contents of a `repomd.xml` file.
+    pub fn parse(input: impl io::BufRead) -> Result<Self, Error> {
+        reader::parse_repomd(input)
+    }
+
+    /// The metadata file of the given type, e.g. `primary`.
+    pub fn get_data(&self, data_type: &str) -> Option<&RepomdData> {
+        self.data.iter().find(|data| data.data_type == data_type)
+    }
+}
+
+/// The metadata of an existing repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repodata {
+    pub repomd: Repomd,
+    /// All packages of the repository, in the order they are listed in
+    pub packages: Vec<PackageRecord>,
+}
+
+impl Repodata {
+    /// Read the metadata of the repository in the given directory.
+    ///
+    /// `repomd.xml` and `primary.xml` are required, the file lists and changelogs are read from
+    /// `filelists.xml` and `other.xml` if present. Metadata files may be uncompressed, or
+    /// compressed with gzip, xz, zstd or bzip2, and their checksums are verified.
+    pub fn open(repo_dir: impl AsRef<Path>) -> Result<Self, Error> {
+        let repo_dir = repo_dir.as_ref();
+        let repomd = Repomd::open(repo_dir)?;
+
+        let primary = repomd.get_data("primary").ok_or_else(|| {
+            Error::InvalidRepodata("repomd.xml does not reference primary.xml".to_owned())
+        })?;
+        let mut packages = reader::parse_primary(reader::open_metadata_file(repo_dir, primary)?)?;
+
+        if let Some(filelists) = repomd.get_data("filelists") {
+            let mut files =
+                reader::parse_filelists(reader::open_metadata_file(repo_dir, filelists)?)?;
+            for package in &mut packages {
+                if let Some(files) = files.remove(&package.checksum) {
+                    package.files = files;
+                }
+            }
+        }
+        if let Some(other) = repomd.get_data("other") {
+            let mut changelogs = reader::parse_other(reader::open_metadata_file(repo_dir, other)?)?;
+            for package in &mut packages {
+                if let Some(changelogs) = changelogs.remove(&package.checksum) {
+                    package.changelogs = changelogs;
+                }
+            }
+        }
+
+        Ok(Repodata { repomd, packages })
+    }
+}
+
+/// Builder for the metadata of a yum/dnf repository.
+pub struct RepodataBuilder {
+    packages: Vec<PackageRecord>,
+    compression: CompressionWithLevel,
+    changelog_limit: usize,
+    timestamp: Option<u64>,
+}
+
+impl Default for RepodataBuilder {
+    fn default() -> Self {
+        Self {
+            packages: Vec::new(),
+            compression: CompressionWithLevel::default(),
+            changelog_limit: DEFAULT_CHANGELOG_LIMIT,
+            timestamp: None,
+        }
+    }
+}
+
+impl RepodataBuilder {
+    /// Create a builder for an empty repository.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a builder containing every `.rpm` file found (recursively) below `repo_dir`.
+    ///
+    /// The package locations are relative to `repo_dir`, so the metadata should be written to the
+    /// same directory.
+    pub fn from_directory(repo_dir: impl AsRef<Path>) -> Result<Self, Error> {
+        let repo_dir = repo_dir.as_ref();
+        let mut builder = Self::new();
+        for path in find_packages(repo_dir)? {
+            let href = relative_href(repo_dir, &path);
+            builder = builder.add_package_file(&path, href)?;
+        }
+        Ok(builder)
+    }
+
+    /// Like [`RepodataBuilder::from_directory`], but reuse the entries of the metadata already
+    /// present in `repo_dir`, like `createrepo_c --update`.
+    ///
+    /// Size and modification time are the change test: packages whose location, size and
+    /// modification time match an existing entry are neither read nor checksummed again, and their
+    /// existing entry (including its checksum) is kept as is. The stored checksum is not
+    /// re-verified, so a package rewritten in place with the same size and modification time is
+    /// not detected. Only new or changed packages are parsed, and entries of packages which no
+    /// longer exist are dropped. If the directory has no metadata yet, all packages are parsed.
+    pub fn update_from_directory(repo_dir: impl AsRef<Path>) -> Result<Self, Error> {
+        let repo_dir = repo_dir.as_ref();
+        let mut existing: HashMap<String, PackageRecord> =
+            if repo_dir.join("repodata").join("repomd.xml").exists() {
+                Repodata::open(repo_dir)?
+                    .packages
+                    .into_iter()
+                    .map(|record| (record.location_href.clone(), record))
+                    .collect()
+            } else {
+                HashMap::new()
+            };
+
+        let mut builder = Self::new();
+        for path in find_packages(repo_dir)? {
+            let href = relative_href(repo_dir, &path);
+            let file_metadata = fs::metadata(&path)?;
+            let file_time = modification_time(&file_metadata)?;
+            // entries using another checksum type than the one generated here are regenerated, so
+            // that all entries of the new metadata use the same type
+            match existing.remove(&href) {
+                Some(record)
+                    if record.package_size == file_metadata.len()
+                        && record.file_time == file_time
+                        && record.checksum_type == "sha256" =>
+                {
+                    builder = builder.add_record(record);
+                }
+                _ => {
+                    log::debug!("Reading new or changed package {}", href);
+                    builder = builder.add_package_file(&path, href)?;
+                }
+            }
+        }
+        Ok(builder)
+    }
+
+    /// Add a package file, which is located at `location_href` relative to the repository root.
+    pub fn add_package_file(
+        mut self,
+        path: impl AsRef<Path>,
+        location_href: impl Into<String>,
+    ) -> Result<Self, Error> {
+        self.packages
+            .push(PackageRecord::from_package_file(path, location_href)?);
+        Ok(self)
+    }
+
+    /// Add a package, which is located at `location_href` relative to the repository root.
+    pub fn add_package(
+        mut self,
+        package: &Package,
+        location_href: impl Into<String>,
+    ) -> Result<Self, Error> {
+        self.packages
+            .push(PackageRecord::from_package(package, location_href)?);
+        Ok(self)
+    }
+
+    /// Add a package record, e.g. taken from existing repository metadata.
+    pub fn add_record(mut self, record: PackageRecord) -> Self {
+        self.packages.push(record);
+        self
+    }
+
+    /// Set the compression of the metadata files, gzip by default.
+    pub fn compression(mut self, compression: impl Into<CompressionWithLevel>) -> Self {
+        self.compression = compression.into();
+        self
+    }
+
+    /// Set the maximum number of changelog entries per package, [`DEFAULT_CHANGELOG_LIMIT`] by
+    /// default.
+    pub fn changelog_limit(mut self, limit: usize) -> Self {
+        self.changelog_limit = limit;
+        self
+    }
+
+    /// Set the timestamp (and revision) of the metadata, the current time by default.
+    ///
+    /// Setting it allows generating reproducible metadata.
+    pub fn timestamp(mut self, timestamp: u64) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    /// The records of all packages added so far.
+    pub fn records(&self) -> &[PackageRecord] {
+        &self.packages
+    }
+
+    /// Write the metadata to the `repodata/` directory below `repo_dir`, replacing any existing
+    /// metadata.
+    pub fn write(&self, repo_dir: impl AsRef<Path>) -> Result<(), Error> {
+        let timestamp = self.timestamp.unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default()
+        });
+
+        // write to a temporary directory first, so that the existing metadata is only replaced
+        // once the new metadata is complete
+        let repo_dir = repo_dir.as_ref();
+        let tmp_dir = repo_dir.join(".repodata");
+        if tmp_dir.exists() {
+            fs::remove_dir_all(&tmp_dir)?;
+        }
+        fs::create_dir_all(&tmp_dir)?;
+
+        let files = [
+            ("primary", writer::primary_xml(&self.packages)?),
+            ("filelists", writer::filelists_xml(&self.packages)?),
+            (
+                "other",
+                writer::other_xml(&self.packages, self.changelog_limit)?,
+            ),
+        ];
+        let mut entries = Vec::with_capacity(files.len());
+        for (data_type, content) in files {
+            entries.push(writer::write_metadata_file(
+                &tmp_dir,
+                data_type,
+                &content,
+                self.compression,
+                timestamp,
+            )?);
+        }
+        fs::write(
+            tmp_dir.join("repomd.xml"),
+            writer::repomd_xml(&entries, timestamp)?,
+        )?;
+
+        let repodata_dir = repo_dir.join("repodata");
+        if repodata_dir.exists() {
+            fs::remove_dir_all(&repodata_dir)?;
+        }
+        fs::rename(&tmp_dir, &repodata_dir)?;
+        Ok(())
+    }
+}
+
+fn relative_href(repo_dir: &Path, path: &Path) -> String {
+    path.strip_prefix(repo_dir)
+        .expect("package paths start with the repository directory")
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn modification_time(metadata: &fs::Metadata) -> Result<u64, Error> {
+    Ok(metadata
+        .modified()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default())
+}
+
+/// Find all `.rpm` files below a directory, sorted by path.
+///
+/// Symlinks to directories are not followed, as they may point back to an ancestor.
+fn find_packages(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut packages = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                dirs.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "rpm") {
+                packages.push(path);
+            }
+        }
+    }
+    packages.sort();
+    Ok(packages)
+}
@@ -0,0 +1,408 @@
+//! Parsing of existing repository metadata files.
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, BufRead},
+    path::Path,
+};
+
+use digest::Digest;
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+
+use super::{PackageRecord, RepoFile, RepoFileType, Repomd, RepomdData};
+use crate::{
+    decompress_stream, errors::*, ChangelogEntry, CompressionType, Dependency, DependencyFlags,
+};
+
+/// A minimal XML element tree, only ever built for a single package at a time.
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Element>,
+    text: String,
+}
+
+impl Element {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn required_attribute(&self, name: &str) -> Result<&str, Error> {
+        self.attribute(name).ok_or_else(|| {
+            Error::InvalidRepodata(format!(
+                "missing attribute {} on element <{}>",
+                name, self.name
+            ))
+        })
+    }
+
+    fn numeric_attribute(&self, name: &str) -> Result<u64, Error> {
+        match self.attribute(name) {
+            Some(value) => value.parse().map_err(|_| {
+                Error::InvalidRepodata(format!(
+                    "invalid number {:?} in attribute {} on element <{}>",
+                    value, name, self.name
+                ))
+            }),
+            None => Ok(0),
+        }
+    }
+
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn child_text(&self, name: &str) -> String {
+        self.child(name)
+            .map(|child| child.text.clone())
+            .unwrap_or_default()
+    }
+
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+}
+
+fn new_element(start: &BytesStart) -> Result<Element, Error> {
+    let mut element = Element {
+        name: String::from_utf8_lossy(start.name().as_ref()).into_owned(),
+        ..Default::default()
+    };
+    for attribute in start.attributes() {
+        let attribute = attribute.map_err(quick_xml::Error::from)?;
+        element.attributes.push((
+            String::from_utf8_lossy(attribute.key.as_ref()).into_owned(),
+            attribute.unescape_value()?.into_owned(),
+        ));
+    }
+    Ok(element)
+}
+
+/// Read the rest of an element whose start tag has just been read.
+fn read_element<R: BufRead>(
+    reader: &mut Reader<R>,
+    buf: &mut Vec<u8>,
+    mut element: Element,
+) -> Result<Element, Error> {
+    loop {
+        buf.clear();
+        match reader.read_event_into(buf)? {
+            Event::Start(start) => {
+                let child = new_element(&start)?;
+                let child = read_element(reader, buf, child)?;
+                element.children.push(child);
+            }
+            Event::Empty(start) => {
+                let child = new_element(&start)?;
+                element.children.push(child);
+            }
+            Event::Text(text) => element.text.push_str(&text.unescape()?),
+            Event::CData(data) => element
+                .text
+                .push_str(&String::from_utf8_lossy(&data.into_inner())),
+            Event::End(_) => return Ok(element),
+            Event::Eof => {
+                return Err(Error::InvalidRepodata(format!(
+                    "unexpected end of file in element <{}>",
+                    element.name
+                )))
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Call `f` with every `<package>` element of a metadata file.
+fn for_each_package<R: BufRead>(
+    input: R,
+    mut f: impl FnMut(Element) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let mut reader = Reader::from_reader(input);
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(start) if start.name().as_ref() == b"package" => {
+                let element = new_element(&start)?;
+                let element = read_element(&mut reader, &mut buf, element)?;
+                f(element)?;
+            }
+            Event::Empty(start) if start.name().as_ref() == b"package" => {
+                f(new_element(&start)?)?;
+            }
+            Event::Eof => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+/// Read a complete (small) metadata file, such as `repomd.xml`, into a tree.
+fn read_document<R: BufRead>(input: R) -> Result<Element, Error> {
+    let mut reader = Reader::from_reader(input);
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(start) => {
+                let element = new_element(&start)?;
+                return read_element(&mut reader, &mut buf, element);
+            }
+            Event::Empty(start) => return new_element(&start),
+            Event::Eof => return Err(Error::InvalidRepodata("no root element found".to_owned())),
+            _ => {}
+        }
+    }
+}
+
+pub(super) fn parse_repomd<R: BufRead>(input: R) -> Result<Repomd, Error> {
+    let root = read_document(input)?;
+    if root.name != "repomd" {
+        return Err(Error::InvalidRepodata(format!(
+            "expected <repomd> but found <{}>",
+            root.name
+        )));
+    }
+
+    let checksum = |element: Option<&Element>| {
+        element.map(|e| {
+            (
+                e.attribute("type").unwrap_or_default().to_owned(),
+                e.text.trim().to_owned(),
+            )
+        })
+    };
+    let number = |element: &Element, name: &str| {
+        element
+            .child(name)
+            .and_then(|child| child.text.trim().parse::<u64>().ok())
+    };
+
+    let mut data = Vec::new();
+    for element in root.children("data") {
+        let location = element
+            .child("location")
+            .ok_or_else(|| Error::InvalidRepodata("missing <location> in repomd.xml".to_owned()))?;
+        data.push(RepomdData {
+            data_type: element.required_attribute("type")?.to_owned(),
+            location_href: location.required_attribute("href")?.to_owned(),
+            checksum: checksum(element.child("checksum")).ok_or_else(|| {
+                Error::InvalidRepodata("missing <checksum> in repomd.xml".to_owned())
+            })?,
+            open_checksum: checksum(element.child("open-checksum")),
+            timestamp: number(element, "timestamp"),
+            size: number(element, "size"),
+            open_size: number(element, "open-size"),
+        });
+    }
+
+    Ok(Repomd {
+        revision: root.child_text("revision").trim().to_owned(),
+        data,
+    })
+}
+
+/// Open a metadata file referenced by `repomd.xml`, verifying its checksum and decompressing it.
+pub(super) fn open_metadata_file(
+    repo_dir: &Path,
+    data: &RepomdData,
+) -> Result<Box<dyn BufRead>, Error> {
+    let path = repo_dir.join(&data.location_href);
+    let content = fs::read(&path)?;
+
+    let (checksum_type, expected) = &data.checksum;
+    let actual = match checksum_type.as_str() {
+        "sha" | "sha1" => Some(hex::encode(sha1::Sha1::digest(&content))),
+        "sha224" => Some(hex::encode(sha2::Sha224::digest(&content))),
+        "sha256" => Some(hex::encode(sha2::Sha256::digest(&content))),
+        "sha384" => Some(hex::encode(sha2::Sha384::digest(&content))),
+        "sha512" => Some(hex::encode(sha2::Sha512::digest(&content))),
+        "md5" => Some(hex::encode(md5::Md5::digest(&content))),
+        _ => None,
+    };
+    if actual.is_some_and(|actual| !actual.eq_ignore_ascii_case(expected)) {
+        return Err(Error::RepodataChecksumMismatch {
+            path: path.display().to_string(),
+        });
+    }
+
+    let href = data.location_href.as_str();
+    let compression = [
+        (".gz", CompressionType::Gzip),
+        (".xz", CompressionType::Xz),
+        (".zst", CompressionType::Zstd),
+        (".bz2", CompressionType::Bzip2),
+        (".lzma", CompressionType::Lzma),
+    ]
+    .into_iter()
+    .find(|(extension, _)| href.ends_with(extension))
+    .map_or(CompressionType::None, |(_, compression)| compression);
+    let reader = decompress_stream(compression, io::Cursor::new(content))?;
+    Ok(Box::new(io::BufReader::new(reader)))
+}
+
+fn parse_dependency(entry: &Element) -> Result<Dependency, Error> {
+    let mut flags = match entry.attribute("flags") {
+        None => DependencyFlags::ANY,
+        Some("EQ") => DependencyFlags::EQUAL,
+        Some("LT") => DependencyFlags::LESS,
+        Some("GT") => DependencyFlags::GREATER,
+        Some("LE") => DependencyFlags::LE,
+        Some("GE") => DependencyFlags::GE,
+        Some(other) => {
+            return Err(Error::InvalidRepodata(format!(
+                "unknown dependency flags {}",
+                other
+            )))
+        }
+    };
+    if entry.attribute("pre") == Some("1") {
+        flags |= DependencyFlags::PREREQ;
+    }
+
+    // rpm headers usually don't contain the epoch if it is 0, so don't add it here either
+    let mut version = String::new();
+    if let Some(ver) = entry.attribute("ver") {
+        match entry.attribute("epoch") {
+            Some(epoch) if epoch != "0" && !epoch.is_empty() => {
+                version = format!("{}:{}", epoch, ver)
+            }
+            _ => version.push_str(ver),
+        }
+        if let Some(rel) = entry.attribute("rel") {
+            version.push('-');
+            version.push_str(rel);
+        }
+    }
+
+    Ok(Dependency {
+        name: entry.required_attribute("name")?.to_owned(),
+        flags,
+        version,
+    })
+}
+
+fn parse_dependencies(format: &Element, name: &str) -> Result<Vec<Dependency>, Error> {
+    match format.child(name) {
+        Some(list) => list.children("rpm:entry").map(parse_dependency).collect(),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn parse_file(file: &Element) -> RepoFile {
+    RepoFile {
+        path: file.text.clone(),
+        file_type: match file.attribute("type") {
+            Some("dir") => RepoFileType::Dir,
+            Some("ghost") => RepoFileType::Ghost,
+            _ => RepoFileType::File,
+        },
+    }
+}
+
+fn parse_primary_package(package: &Element) -> Result<PackageRecord, Error> {
+    let missing = |name: &str| Error::InvalidRepodata(format!("missing <{}> in package", name));
+    let version = package.child("version").ok_or_else(|| missing("version"))?;
+    let checksum = package
+        .child("checksum")
+        .ok_or_else(|| missing("checksum"))?;
+    let time = package.child("time").ok_or_else(|| missing("time"))?;
+    let size = package.child("size").ok_or_else(|| missing("size"))?;
+    let location = package
+        .child("location")
+        .ok_or_else(|| missing("location"))?;
+    let empty = Element::default();
+    let format = package.child("format").unwrap_or(&empty);
+    let header_range = format.child("rpm:header-range").unwrap_or(&empty);
+
+    Ok(PackageRecord {
+        name: package.child_text("name"),
+        arch: package.child_text("arch"),
+        epoch: version.numeric_attribute("epoch")? as u32,
+        version: version.required_attribute("ver")?.to_owned(),
+        release: version.attribute("rel").unwrap_or_default().to_owned(),
+        checksum_type: checksum.attribute("type").unwrap_or_default().to_owned(),
+        checksum: checksum.text.trim().to_owned(),
+        summary: package.child_text("summary"),
+        description: package.child_text("description"),
+        packager: package.child_text("packager"),
+        url: package.child_text("url"),
+        file_time: time.numeric_attribute("file")?,
+        build_time: time.numeric_attribute("build")?,
+        package_size: size.numeric_attribute("package")?,
+        installed_size: size.numeric_attribute("installed")?,
+        archive_size: size.numeric_attribute("archive")?,
+        location_href: location.required_attribute("href")?.to_owned(),
+        license: format.child_text("rpm:license"),
+        vendor: format.child_text("rpm:vendor"),
+        group: format.child_text("rpm:group"),
+        build_host: format.child_text("rpm:buildhost"),
+        source_rpm: format.child_text("rpm:sourcerpm"),
+        header_range: (
+            header_range.numeric_attribute("start")?,
+            header_range.numeric_attribute("end")?,
+        ),
+        provides: parse_dependencies(format, "rpm:provides")?,
+        requires: parse_dependencies(format, "rpm:requires")?,
+        conflicts: parse_dependencies(format, "rpm:conflicts")?,
+        obsoletes: parse_dependencies(format, "rpm:obsoletes")?,
+        recommends: parse_dependencies(format, "rpm:recommends")?,
+        suggests: parse_dependencies(format, "rpm:suggests")?,
+        enhances: parse_dependencies(format, "rpm:enhances")?,
+        supplements: parse_dependencies(format, "rpm:supplements")?,
+        files: format.children("file").map(parse_file).collect(),
+        changelogs: Vec::new(),
+    })
+}
+
+pub(super) fn parse_primary<R: BufRead>(input: R) -> Result<Vec<PackageRecord>, Error> {
+    let mut records = Vec::new();
+    for_each_package(input, |package| {
+        records.push(parse_primary_package(&package)?);
+        Ok(())
+    })?;
+    Ok(records)
+}
+
+/// The files of every package in `filelists.xml`, by package id.
+pub(super) fn parse_filelists<R: BufRead>(
+    input: R,
+) -> Result<HashMap<String, Vec<RepoFile>>, Error> {
+    let mut files = HashMap::new();
+    for_each_package(input, |package| {
+        let pkgid = package.required_attribute("pkgid")?.to_owned();
+        files.insert(pkgid, package.children("file").map(parse_file).collect());
+        Ok(())
+    })?;
+    Ok(files)
+}
+
+/// The changelog entries of every package in `other.xml`, newest first, by package id.
+pub(super) fn parse_other<R: BufRead>(
+    input: R,
+) -> Result<HashMap<String, Vec<ChangelogEntry>>, Error> {
+    let mut changelogs = HashMap::new();
+    for_each_package(input, |package| {
+        let pkgid = package.required_attribute("pkgid")?.to_owned();
+        let mut entries = package
+            .children("changelog")
+            .map(|entry| {
+                Ok(ChangelogEntry {
+                    name: entry.attribute("author").unwrap_or_default().to_owned(),
+                    timestamp: entry.numeric_attribute("date")?,
+                    description: entry.text.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        entries.reverse();
+        changelogs.insert(pkgid, entries);
+        Ok(())
+    })?;
+    Ok(changelogs)
+}
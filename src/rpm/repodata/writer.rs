@@ -0,0 +1,376 @@
+//! Serialization of the repository metadata files.
+use std::{fs, io::Write, path::Path};
+
+use digest::Digest;
+use quick_xml::{
+    events::{BytesDecl, BytesText, Event},
+    Writer,
+};
+
+use super::{PackageRecord, RepoFileType};
+use crate::{
+    errors::*, rpm::dependencies::is_prerequisite, CompressionType, CompressionWithLevel,
+    Compressor, Dependency, DependencyFlags, Evr,
+};
+
+const XMLNS_COMMON: &str = "http://linux.duke.edu/metadata/common";
+const XMLNS_FILELISTS: &str = "http://linux.duke.edu/metadata/filelists";
+const XMLNS_OTHER: &str = "http://linux.duke.edu/metadata/other";
+const XMLNS_REPO: &str = "http://linux.duke.edu/metadata/repo";
+const XMLNS_RPM: &str = "http://linux.duke.edu/metadata/rpm";
+
+type XmlWriter = Writer<Vec<u8>>;
+
+/// A metadata file which has been written, as referenced from `repomd.xml`.
+pub(super) struct RepomdEntry {
+    data_type: &'static str,
+    href: String,
+    checksum: String,
+    size: u64,
+    /// The checksum and size of the uncompressed file, if it is compressed
+    open: Option<(String, u64)>,
+    timestamp: u64,
+}
+
+fn new_document() -> Result<XmlWriter, Error> {
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+    Ok(writer)
+}
+
+fn finish_document(writer: XmlWriter) -> Vec<u8> {
+    let mut content = writer.into_inner();
+    content.push(b'\n');
+    content
+}
+
+fn write_text(writer: &mut XmlWriter, name: &str, text: &str) -> Result<(), Error> {
+    writer
+        .create_element(name)
+        .write_text_content(BytesText::new(text))?;
+    Ok(())
+}
+
+fn write_version(writer: &mut XmlWriter, record: &PackageRecord) -> Result<(), Error> {
+    writer
+        .create_element("version")
+        .with_attributes([
+            ("epoch", record.epoch.to_string().as_str()),
+            ("ver", record.version.as_str()),
+            ("rel", record.release.as_str()),
+        ])
+        .write_empty()?;
+    Ok(())
+}
+
+fn dependency_flags(flags: DependencyFlags) -> Option<&'static str> {
+    let sense = flags & (DependencyFlags::LESS | DependencyFlags::GREATER | DependencyFlags::EQUAL);
+    match sense {
+        DependencyFlags::EQUAL => Some("EQ"),
+        DependencyFlags::LESS => Some("LT"),
+        DependencyFlags::GREATER => Some("GT"),
+        DependencyFlags::LE => Some("LE"),
+        DependencyFlags::GE => Some("GE"),
+        _ => None,
+    }
+}
+
+fn write_dependencies(
+    writer: &mut XmlWriter,
+    name: &str,
+    dependencies: &[Dependency],
+    with_pre: bool,
+) -> Result<(), Error> {
+    if dependencies.is_empty() {
+        return Ok(());
+    }
+    writer
+        .create_element(name)
+        .write_inner_content(|writer| -> Result<(), Error> {
+            for dependency in dependencies {
+                let mut entry = writer
+                    .create_element("rpm:entry")
+                    .with_attribute(("name", dependency.name.as_str()));
+                let flags = dependency_flags(dependency.flags);
+                if let Some(flags) = flags {
+                    entry = entry.with_attribute(("flags", flags));
+                }
+                if flags.is_some() && !dependency.version.is_empty() {
+                    let evr: Evr = dependency.version.parse()?;
+                    entry = entry
+                        .with_attribute(("epoch", evr.epoch_or_default().to_string().as_str()))
+                        .with_attribute(("ver", evr.version.as_str()));
+                    if let Some(release) = &evr.release {
+                        entry = entry.with_attribute(("rel", release.as_str()));
+                    }
+                }
+                if with_pre && is_prerequisite(dependency) {
+                    entry = entry.with_attribute(("pre", "1"));
+                }
+                entry.write_empty()?;
+            }
+            Ok(())
+        })?;
+    Ok(())
+}
+
+fn write_file(writer: &mut XmlWriter, path: &str, file_type: RepoFileType) -> Result<(), Error> {
+    let element = writer.create_element("file");
+    let element = match file_type {
+        RepoFileType::File => element,
+        RepoFileType::Dir => element.with_attribute(("type", "dir")),
+        RepoFileType::Ghost => element.with_attribute(("type", "ghost")),
+    };
+    element.write_text_content(BytesText::new(path))?;
+    Ok(())
+}
+
+/// Whether a file is listed in `primary.xml` as well, as it is commonly required by path.
+fn is_primary_file(path: &str) -> bool {
+    path.starts_with("/etc/") || path == "/usr/lib/sendmail" || path.contains("bin/")
+}
+
+pub(super) fn primary_xml(packages: &[PackageRecord]) -> Result<Vec<u8>, Error> {
+    let mut writer = new_document()?;
+    writer
+        .create_element("metadata")
+        .with_attributes([
+            ("xmlns", XMLNS_COMMON),
+            ("xmlns:rpm", XMLNS_RPM),
+            ("packages", packages.len().to_string().as_str()),
+        ])
+        .write_inner_content(|writer| -> Result<(), Error> {
+            for record in packages {
+                write_primary_package(writer, record)?;
+            }
+            Ok(())
+        })?;
+    Ok(finish_document(writer))
+}
+
+fn write_primary_package(writer: &mut XmlWriter, record: &PackageRecord) -> Result<(), Error> {
+    writer
+        .create_element("package")
+        .with_attribute(("type", "rpm"))
+        .write_inner_content(|writer| -> Result<(), Error> {
+            write_text(writer, "name", &record.name)?;
+            write_text(writer, "arch", &record.arch)?;
+            write_version(writer, record)?;
+            writer
+                .create_element("checksum")
+                .with_attributes([("type", record.checksum_type.as_str()), ("pkgid", "YES")])
+                .write_text_content(BytesText::new(&record.checksum))?;
+            write_text(writer, "summary", &record.summary)?;
+            write_text(writer, "description", &record.description)?;
+            write_text(writer, "packager", &record.packager)?;
+            write_text(writer, "url", &record.url)?;
+            writer
+                .create_element("time")
+                .with_attributes([
+                    ("file", record.file_time.to_string().as_str()),
+                    ("build", record.build_time.to_string().as_str()),
+                ])
+                .write_empty()?;
+            writer
+                .create_element("size")
+                .with_attributes([
+                    ("package", record.package_size.to_string().as_str()),
+                    ("installed", record.installed_size.to_string().as_str()),
+                    ("archive", record.archive_size.to_string().as_str()),
+                ])
+                .write_empty()?;
+            writer
+                .create_element("location")
+                .with_attribute(("href", record.location_href.as_str()))
+                .write_empty()?;
+            writer
+                .create_element("format")
+                .write_inner_content(|writer| -> Result<(), Error> {
+                    write_text(writer, "rpm:license", &record.license)?;
+                    write_text(writer, "rpm:vendor", &record.vendor)?;
+                    write_text(writer, "rpm:group", &record.group)?;
+                    write_text(writer, "rpm:buildhost", &record.build_host)?;
+                    write_text(writer, "rpm:sourcerpm", &record.source_rpm)?;
+                    writer
+                        .create_element("rpm:header-range")
+                        .with_attributes([
+                            ("start", record.header_range.0.to_string().as_str()),
+                            ("end", record.header_range.1.to_string().as_str()),
+                        ])
+                        .write_empty()?;
+                    write_dependencies(writer, "rpm:provides", &record.provides, false)?;
+                    write_dependencies(writer, "rpm:requires", &record.requires, true)?;
+                    write_dependencies(writer, "rpm:conflicts", &record.conflicts, false)?;
+                    write_dependencies(writer, "rpm:obsoletes", &record.obsoletes, false)?;
+                    write_dependencies(writer, "rpm:suggests", &record.suggests, false)?;
+                    write_dependencies(writer, "rpm:enhances", &record.enhances, false)?;
+                    write_dependencies(writer, "rpm:recommends", &record.recommends, false)?;
+                    write_dependencies(writer, "rpm:supplements", &record.supplements, false)?;
+                    for file in record.files.iter().filter(|f| is_primary_file(&f.path)) {
+                        write_file(writer, &file.path, file.file_type)?;
+                    }
+                    Ok(())
+                })?;
+            Ok(())
+        })?;
+    Ok(())
+}
+
+pub(super) fn filelists_xml(packages: &[PackageRecord]) -> Result<Vec<u8>, Error> {
+    let mut writer = new_document()?;
+    writer
+        .create_element("filelists")
+        .with_attributes([
+            ("xmlns", XMLNS_FILELISTS),
+            ("packages", packages.len().to_string().as_str()),
+        ])
+        .write_inner_content(|writer| -> Result<(), Error> {
+            for record in packages {
+                package_element(writer, record).write_inner_content(
+                    |writer| -> Result<(), Error> {
+                        write_version(writer, record)?;
+                        for file in &record.files {
+                            write_file(writer, &file.path, file.file_type)?;
+                        }
+                        Ok(())
+                    },
+                )?;
+            }
+            Ok(())
+        })?;
+    Ok(finish_document(writer))
+}
+
+pub(super) fn other_xml(
+    packages: &[PackageRecord],
+    changelog_limit: usize,
+) -> Result<Vec<u8>, Error> {
+    let mut writer = new_document()?;
+    writer
+        .create_element("otherdata")
+        .with_attributes([
+            ("xmlns", XMLNS_OTHER),
+            ("packages", packages.len().to_string().as_str()),
+        ])
+        .write_inner_content(|writer| -> Result<(), Error> {
+            for record in packages {
+                package_element(writer, record).write_inner_content(
+                    |writer| -> Result<(), Error> {
+                        write_version(writer, record)?;
+                        // the newest entries, oldest first
+                        for entry in record.changelogs.iter().take(changelog_limit).rev() {
+                            writer
+                                .create_element("changelog")
+                                .with_attributes([
+                                    ("author", entry.name.as_str()),
+                                    ("date", entry.timestamp.to_string().as_str()),
+                                ])
+                                .write_text_content(BytesText::new(&entry.description))?;
+                        }
+                        Ok(())
+                    },
+                )?;
+            }
+            Ok(())
+        })?;
+    Ok(finish_document(writer))
+}
+
+fn package_element<'a>(
+    writer: &'a mut XmlWriter,
+    record: &'a PackageRecord,
+) -> quick_xml::writer::ElementWriter<'a, Vec<u8>> {
+    writer.create_element("package").with_attributes([
+        ("pkgid", record.checksum.as_str()),
+        ("name", record.name.as_str()),
+        ("arch", record.arch.as_str()),
+    ])
+}
+
+fn file_extension(compression: CompressionType) -> &'static str {
+    match compression {
+        CompressionType::None => "",
+        CompressionType::Gzip => ".gz",
+        CompressionType::Zstd => ".zst",
+        CompressionType::Xz => ".xz",
+        CompressionType::Bzip2 => ".bz2",
+        CompressionType::Lzma => ".lzma",
+    }
+}
+
+/// Compress and write a metadata file to the given directory, named after its checksum.
+pub(super) fn write_metadata_file(
+    dir: &Path,
+    data_type: &'static str,
+    content: &[u8],
+    compression: CompressionWithLevel,
+    timestamp: u64,
+) -> Result<RepomdEntry, Error> {
+    let mut compressor = Compressor::try_from(compression)?;
+    compressor.write_all(content)?;
+    let compressed = compressor.finish_compression()?;
+
+    let checksum = hex::encode(sha2::Sha256::digest(&compressed));
+    let open = match compression.compression_type() {
+        CompressionType::None => None,
+        _ => Some((
+            hex::encode(sha2::Sha256::digest(content)),
+            content.len() as u64,
+        )),
+    };
+    let file_name = format!(
+        "{}-{}.xml{}",
+        checksum,
+        data_type,
+        file_extension(compression.compression_type())
+    );
+    fs::write(dir.join(&file_name), &compressed)?;
+
+    Ok(RepomdEntry {
+        data_type,
+        href: format!("repodata/{}", file_name),
+        checksum,
+        size: compressed.len() as u64,
+        open,
+        timestamp,
+    })
+}
+
+pub(super) fn repomd_xml(entries: &[RepomdEntry], revision: u64) -> Result<Vec<u8>, Error> {
+    let mut writer = new_document()?;
+    writer
+        .create_element("repomd")
+        .with_attributes([("xmlns", XMLNS_REPO), ("xmlns:rpm", XMLNS_RPM)])
+        .write_inner_content(|writer| -> Result<(), Error> {
+            write_text(writer, "revision", &revision.to_string())?;
+            for entry in entries {
+                writer
+                    .create_element("data")
+                    .with_attribute(("type", entry.data_type))
+                    .write_inner_content(|writer| -> Result<(), Error> {
+                        writer
+                            .create_element("checksum")
+                            .with_attribute(("type", "sha256"))
+                            .write_text_content(BytesText::new(&entry.checksum))?;
+                        if let Some((open_checksum, _)) = &entry.open {
+                            writer
+                                .create_element("open-checksum")
+                                .with_attribute(("type", "sha256"))
+                                .write_text_content(BytesText::new(open_checksum))?;
+                        }
+                        writer
+                            .create_element("location")
+                            .with_attribute(("href", entry.href.as_str()))
+                            .write_empty()?;
+                        write_text(writer, "timestamp", &entry.timestamp.to_string())?;
+                        write_text(writer, "size", &entry.size.to_string())?;
+                        if let Some((_, open_size)) = &entry.open {
+                            write_text(writer, "open-size", &open_size.to_string())?;
+                        }
+                        Ok(())
+                    })?;
+            }
+            Ok(())
+        })?;
+    Ok(finish_document(writer))
+}
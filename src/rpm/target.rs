@@ -0,0 +1,214 @@
+use std::fmt;
+
+use crate::{errors::*, Dependency};
+
+/// A version of rpm, used to describe which features a package can use.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct RpmVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl RpmVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        RpmVersion {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+impl std::str::FromStr for RpmVersion {
+    type Err = Error;
+
+    /// Parse a version like `4.16.1.3`, anything after the patch version is ignored.
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidRpmVersion(raw.to_owned());
+        let mut parts = raw.split('.').map(|part| part.parse::<u32>());
+        let major = parts.next().ok_or_else(invalid)?.map_err(|_| invalid())?;
+        let minor = parts.next().unwrap_or(Ok(0)).map_err(|_| invalid())?;
+        let patch = parts.next().unwrap_or(Ok(0)).map_err(|_| invalid())?;
+        Ok(RpmVersion::new(major, minor, patch))
+    }
+}
+
+impl fmt::Display for RpmVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// The distribution or rpm version a package is built for.
+///
+/// Setting a target with [`crate::PackageBuilder::target`] makes the builder reject features the
+/// target's rpm can't handle, instead of producing a package which fails to install.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Target {
+    /// RHEL / CentOS 7, rpm 4.11
+    El7,
+    /// RHEL / CentOS 8, rpm 4.14
+    El8,
+    /// RHEL / CentOS 9, rpm 4.16
+    El9,
+    /// Fedora 40 and later, rpm 4.19
+    Fedora,
+    /// SUSE Linux Enterprise 15, rpm 4.14
+    Sles15,
+    /// Any other distribution, using the given rpm version
+    Rpm(RpmVersion),
+}
+
+impl Target {
+    /// The version of rpm shipped by the target.
+    pub fn rpm_version(&self) -> RpmVersion {
+        match self {
+            Target::El7 => RpmVersion::new(4, 11, 3),
+            Target::El8 => RpmVersion::new(4, 14, 3),
+            Target::El9 => RpmVersion::new(4, 16, 1),
+            Target::Fedora => RpmVersion::new(4, 19, 1),
+            Target::Sles15 => RpmVersion::new(4, 14, 3),
+            Target::Rpm(version) => *version,
+        }
+    }
+
+    /// Whether the target's rpm has the given version or is newer.
+    pub fn supports(&self, version: RpmVersion) -> bool {
+        self.rpm_version() >= version
+    }
+
+    /// Fail with [`Error::UnsupportedByTarget`] if `feature` requires a newer rpm than the target
+    /// ships.
+    pub(crate) fn require(&self, feature: &str, version: RpmVersion) -> Result<(), Error> {
+        if self.supports(version) {
+            Ok(())
+        } else {
+            Err(Error::UnsupportedByTarget {
+                feature: feature.to_owned(),
+                required: version,
+                target: *self,
+            })
+        }
+    }
+
+    /// Fail with [`Error::UnsupportedByTarget`] if any of the `rpmlib()` requirements is unknown
+    /// to the target's rpm.
+    pub(crate) fn check_rpmlib_requirements(&self, requires: &[Dependency]) -> Result<(), Error> {
+        for dependency in requires {
+            if let Some(feature) = RpmlibFeature::from_dependency(dependency) {
+                self.require(&dependency.name, feature.introduced_in)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::El7 => write!(f, "EL7")?,
+            Target::El8 => write!(f, "EL8")?,
+            Target::El9 => write!(f, "EL9")?,
+            Target::Fedora => write!(f, "Fedora")?,
+            Target::Sles15 => write!(f, "SLES 15")?,
+            Target::Rpm(_) => return write!(f, "rpm {}", self.rpm_version()),
+        }
+        write!(f, " (rpm {})", self.rpm_version())
+    }
+}
+
+/// An rpm feature which packages can depend on with an `rpmlib()` requirement.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct RpmlibFeature {
+    /// Name of the feature, without the `rpmlib()` around it
+    pub name: &'static str,
+    /// The version rpm provides the feature with
+    pub version: &'static str,
+    /// The rpm release which added the feature
+    pub introduced_in: RpmVersion,
+}
+
+impl RpmlibFeature {
+    const fn new(name: &'static str, version: &'static str, introduced_in: RpmVersion) -> Self {
+        RpmlibFeature {
+            name,
+            version,
+            introduced_in,
+        }
+    }
+
+    /// Look up a feature by name, without the `rpmlib()` around it.
+    pub fn from_name(name: &str) -> Option<&'static RpmlibFeature> {
+        RPMLIB_FEATURES.iter().find(|feature| feature.name == name)
+    }
+
+    /// Look up the feature an `rpmlib()` requirement refers to.
+    pub fn from_dependency(dependency: &Dependency) -> Option<&'static RpmlibFeature> {
+        dependency
+            .name
+            .strip_prefix("rpmlib(")
+            .and_then(|name| name.strip_suffix(')'))
+            .and_then(RpmlibFeature::from_name)
+    }
+
+    /// The `rpmlib()` requirement on this feature.
+    pub fn dependency(&self) -> Dependency {
+        Dependency::rpmlib(self.name, self.version)
+    }
+}
+
+/// An `rpmlib()` requirement of a package, see
+/// [`crate::PackageMetadata::get_rpmlib_requirements`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RpmlibRequirement {
+    /// The requirement as recorded in the package
+    pub dependency: Dependency,
+    /// The required feature, `None` if it isn't one of the [`RPMLIB_FEATURES`]
+    pub feature: Option<&'static RpmlibFeature>,
+}
+
+impl RpmlibRequirement {
+    /// Whether the rpm of the target provides the required feature. Unknown features are assumed
+    /// to be unsupported.
+    pub fn is_supported_by(&self, target: Target) -> bool {
+        self.feature.is_some_and(|feature| {
+            target.supports(feature.introduced_in)
+                && self.dependency.overlaps(&feature.dependency())
+        })
+    }
+}
+
+/// The known `rpmlib()` features along with the rpm release which added them. The versions rpm
+/// provides the features with don't necessarily match the release.
+pub const RPMLIB_FEATURES: &[RpmlibFeature] = &[
+    RpmlibFeature::new("VersionedDependencies", "3.0.3-1", RpmVersion::new(3, 0, 3)),
+    RpmlibFeature::new("CompressedFileNames", "3.0.4-1", RpmVersion::new(3, 0, 4)),
+    RpmlibFeature::new("PayloadIsBzip2", "3.0.5-1", RpmVersion::new(3, 0, 5)),
+    RpmlibFeature::new("PayloadFilesHavePrefix", "4.0-1", RpmVersion::new(4, 0, 0)),
+    RpmlibFeature::new("ExplicitPackageProvide", "4.0-1", RpmVersion::new(4, 0, 0)),
+    RpmlibFeature::new("HeaderLoadSortsTags", "4.0.1-1", RpmVersion::new(4, 0, 1)),
+    RpmlibFeature::new(
+        "ScriptletInterpreterArgs",
+        "4.0.3-1",
+        RpmVersion::new(4, 0, 3),
+    ),
+    RpmlibFeature::new("PartialHardlinkSets", "4.0.4-1", RpmVersion::new(4, 0, 4)),
+    RpmlibFeature::new("ConcurrentAccess", "4.1-1", RpmVersion::new(4, 1, 0)),
+    RpmlibFeature::new("BuiltinLuaScripts", "4.2.2-1", RpmVersion::new(4, 2, 2)),
+    RpmlibFeature::new("PayloadIsLzma", "4.4.6-1", RpmVersion::new(4, 6, 0)),
+    RpmlibFeature::new("FileDigests", "4.6.0-1", RpmVersion::new(4, 6, 0)),
+    RpmlibFeature::new("FileCaps", "4.6.1-1", RpmVersion::new(4, 6, 1)),
+    RpmlibFeature::new("PayloadIsXz", "5.2-1", RpmVersion::new(4, 7, 0)),
+    RpmlibFeature::new("ScriptletExpansion", "4.9.0-1", RpmVersion::new(4, 9, 0)),
+    RpmlibFeature::new("TildeInVersions", "4.10.0-1", RpmVersion::new(4, 10, 0)),
+    RpmlibFeature::new("LargeFiles", "4.12.0-1", RpmVersion::new(4, 12, 0)),
+    RpmlibFeature::new("RichDependencies", "4.12.0-1", RpmVersion::new(4, 12, 0)),
+    RpmlibFeature::new("PayloadIsZstd", "5.4.18-1", RpmVersion::new(4, 14, 0)),
+    RpmlibFeature::new("CaretInVersions", "4.15.0-1", RpmVersion::new(4, 15, 0)),
+    RpmlibFeature::new(
+        "DynamicBuildRequires",
+        "4.15.0-1",
+        RpmVersion::new(4, 15, 0),
+    ),
+];
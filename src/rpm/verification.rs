@@ -0,0 +1,277 @@
+use std::fmt;
+
+use crate::DigestAlgorithm;
+
+#[cfg(feature = "signature-pgp")]
+use crate::{signature::pgp::SignatureInfo, IndexSignatureTag};
+
+/// Outcome of checking a single digest or signature of a package.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum VerificationStatus {
+    /// The package doesn't carry the digest or signature
+    NotPresent,
+    /// The digest matches the package, or the signature was verified
+    Verified,
+    /// The signature was made by a key the verifier doesn't have
+    NoKey,
+    /// The digest doesn't match the package, or the signature is invalid
+    Bad,
+}
+
+impl VerificationStatus {
+    /// Whether the digest or signature is present in the package.
+    pub fn is_present(&self) -> bool {
+        *self != VerificationStatus::NotPresent
+    }
+}
+
+impl fmt::Display for VerificationStatus {
+    /// The status as shown by `rpmkeys -Kv`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerificationStatus::NotPresent => write!(f, "NOTFOUND"),
+            VerificationStatus::Verified => write!(f, "OK"),
+            VerificationStatus::NoKey => write!(f, "NOKEY"),
+            VerificationStatus::Bad => write!(f, "BAD"),
+        }
+    }
+}
+
+/// The digests a package can carry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DigestKind {
+    /// `RPMSIGTAG_SHA3_256`, spanning the header, as defined by rpm 6
+    HeaderSha3_256,
+    /// `RPMSIGTAG_SHA256`, spanning the header
+    HeaderSha256,
+    /// `RPMSIGTAG_SHA1`, spanning the header
+    HeaderSha1,
+    /// `RPMTAG_PAYLOADDIGEST`, spanning the compressed payload, with the algorithm from
+    /// `RPMTAG_PAYLOADDIGESTALGO`
+    Payload(Option<DigestAlgorithm>),
+    /// `RPMSIGTAG_MD5`, spanning the header and payload
+    Md5,
+}
+
+impl fmt::Display for DigestKind {
+    /// The name of the digest as shown by `rpmkeys -Kv`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DigestKind::HeaderSha3_256 => write!(f, "Header SHA3-256 digest"),
+            DigestKind::HeaderSha256 => write!(f, "Header SHA256 digest"),
+            DigestKind::HeaderSha1 => write!(f, "Header SHA1 digest"),
+            DigestKind::Payload(algorithm) => {
+                let algorithm = match algorithm {
+                    Some(DigestAlgorithm::Md5) => "MD5",
+                    Some(DigestAlgorithm::Sha2_224) => "SHA224",
+                    Some(DigestAlgorithm::Sha2_256) => "SHA256",
+                    Some(DigestAlgorithm::Sha2_384) => "SHA384",
+                    Some(DigestAlgorithm::Sha2_512) => "SHA512",
+                    None => "unknown",
+                };
+                write!(f, "Payload {} digest", algorithm)
+            }
+            DigestKind::Md5 => write!(f, "MD5 digest"),
+        }
+    }
+}
+
+/// The result of checking one of the digests of a package, see [`crate::Package::check_digests`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DigestCheck {
+    pub kind: DigestKind,
+    pub status: VerificationStatus,
+}
+
+impl fmt::Display for DigestCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind, self.status)
+    }
+}
+
+/// The result of checking one of the signatures of a package.
+#[cfg(feature = "signature-pgp")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignatureCheck {
+    /// The signature header tag holding the signature
+    pub tag: IndexSignatureTag,
+    /// Details of the signature, `None` if it isn't present or can't be parsed
+    pub info: Option<SignatureInfo>,
+    pub status: VerificationStatus,
+}
+
+#[cfg(feature = "signature-pgp")]
+impl SignatureCheck {
+    pub(crate) fn not_present(tag: IndexSignatureTag) -> Self {
+        SignatureCheck {
+            tag,
+            info: None,
+            status: VerificationStatus::NotPresent,
+        }
+    }
+}
+
+#[cfg(feature = "signature-pgp")]
+impl fmt::Display for SignatureCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.info {
+            Some(info) => write!(f, "{}: {}", info, self.status),
+            None => write!(f, "{} signature: {}", self.tag, self.status),
+        }
+    }
+}
+
+/// Report on all digests and signatures of a package, see
+/// [`crate::Package::verification_report`].
+///
+/// Unlike [`crate::Package::verify_signature`], which fails on the first problem, the report
+/// tells apart digests and signatures which are missing from the ones which failed to verify.
+/// Its [`Display`](fmt::Display) implementation mirrors the output of `rpmkeys -Kv`.
+#[cfg(feature = "signature-pgp")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerificationReport {
+    /// The signatures, spanning either the header only or the header and payload. The signature
+    /// tags are listed even if not present, the rpm 6 `RPMSIGTAG_OPENPGP` tag has an entry per
+    /// signature it holds, except for the copy of the signature in `RPMSIGTAG_RSA` or
+    /// `RPMSIGTAG_DSA`.
+    pub signatures: Vec<SignatureCheck>,
+    /// The digests, all of them are listed even if not present
+    pub digests: Vec<DigestCheck>,
+}
+
+#[cfg(feature = "signature-pgp")]
+impl VerificationReport {
+    /// Whether the package carries any signature, regardless of whether it was verified.
+    pub fn is_signed(&self) -> bool {
+        self.signatures
+            .iter()
+            .any(|signature| signature.status.is_present())
+    }
+
+    /// Whether at least one signature was verified, and none of the digests and signatures is
+    /// bad.
+    pub fn is_verified(&self) -> bool {
+        !self.has_bad()
+            && self
+                .signatures
+                .iter()
+                .any(|signature| signature.status == VerificationStatus::Verified)
+    }
+
+    /// Whether any digest or signature doesn't match the package.
+    pub fn has_bad(&self) -> bool {
+        self.signatures
+            .iter()
+            .map(|signature| signature.status)
+            .chain(self.digests.iter().map(|digest| digest.status))
+            .any(|status| status == VerificationStatus::Bad)
+    }
+
+    /// Whether the integrity of the package rests on its MD5 digest alone, i.e. it carries
+    /// neither a signature nor any other digest.
+    pub fn is_md5_only(&self) -> bool {
+        !self.is_signed()
+            && self
+                .digests
+                .iter()
+                .all(|digest| (digest.kind == DigestKind::Md5) == digest.status.is_present())
+    }
+}
+
+#[cfg(feature = "signature-pgp")]
+impl fmt::Display for VerificationReport {
+    /// Lists the present digests and signatures like `rpmkeys -Kv` does, the ones spanning the
+    /// header first.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (header_only, header_and_payload): (Vec<&SignatureCheck>, Vec<&SignatureCheck>) = self
+            .signatures
+            .iter()
+            .filter(|signature| signature.status.is_present())
+            .partition(|signature| {
+                !matches!(
+                    signature.tag,
+                    IndexSignatureTag::RPMSIGTAG_PGP | IndexSignatureTag::RPMSIGTAG_GPG
+                )
+            });
+        let digests = self
+            .digests
+            .iter()
+            .filter(|digest| digest.status.is_present());
+        let (md5, digests): (Vec<&DigestCheck>, Vec<&DigestCheck>) =
+            digests.partition(|digest| digest.kind == DigestKind::Md5);
+
+        for signature in header_only {
+            writeln!(f, "    {}", signature)?;
+        }
+        for digest in digests {
+            writeln!(f, "    {}", digest)?;
+        }
+        for signature in header_and_payload {
+            writeln!(f, "    {}", signature)?;
+        }
+        for digest in md5 {
+            writeln!(f, "    {}", digest)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "signature-pgp"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_md5_only() {
+        let digests = |present: &[DigestKind]| {
+            [
+                DigestKind::HeaderSha3_256,
+                DigestKind::HeaderSha256,
+                DigestKind::HeaderSha1,
+                DigestKind::Payload(None),
+                DigestKind::Md5,
+            ]
+            .into_iter()
+            .map(|kind| DigestCheck {
+                kind,
+                status: if present.contains(&kind) {
+                    VerificationStatus::Verified
+                } else {
+                    VerificationStatus::NotPresent
+                },
+            })
+            .collect()
+        };
+        let unsigned = [SignatureCheck::not_present(
+            IndexSignatureTag::RPMSIGTAG_RSA,
+        )];
+
+        let report = VerificationReport {
+            signatures: unsigned.to_vec(),
+            digests: digests(&[DigestKind::Md5]),
+        };
+        assert!(report.is_md5_only());
+        assert!(!report.is_signed());
+        assert_eq!(report.to_string(), "    MD5 digest: OK\n");
+
+        let report = VerificationReport {
+            signatures: unsigned.to_vec(),
+            digests: digests(&[DigestKind::HeaderSha256, DigestKind::Md5]),
+        };
+        assert!(!report.is_md5_only());
+
+        let report = VerificationReport {
+            signatures: vec![SignatureCheck {
+                tag: IndexSignatureTag::RPMSIGTAG_PGP,
+                info: None,
+                status: VerificationStatus::NoKey,
+            }],
+            digests: digests(&[DigestKind::Md5]),
+        };
+        assert!(!report.is_md5_only());
+        assert!(report.is_signed());
+        assert_eq!(
+            report.to_string(),
+            "    RPMSIGTAG_PGP signature: NOKEY\n    MD5 digest: OK\n"
+        );
+    }
+}
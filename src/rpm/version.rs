@@ -0,0 +1,600 @@
+//! Package versions and identifiers, following the semantics of rpm's `rpmvercmp()`.
+use std::{cmp::Ordering, fmt::Display, path::Path, str::FromStr};
+
+use crate::errors::*;
+
+/// Compare two version (or release) strings the same way rpm does.
+///
+/// The strings are split into alternating runs of digits and letters, every other character
+/// only acts as a separator. Numeric runs are compared numerically and always sort higher than
+/// alphabetic runs. A `~` sorts lower than anything else, including the end of the string, which
+/// is what makes `1.0~rc1` older than `1.0`. A `^` sorts higher than the end of the string but
+/// lower than anything else, which makes `1.0^git1` newer than `1.0` but older than `1.0.1`.
+///
+/// ```
+/// use std::cmp::Ordering;
+///
+/// assert_eq!(rpm::rpmvercmp("1.0~rc1", "1.0"), Ordering::Less);
+/// assert_eq!(rpm::rpmvercmp("1.0^git1", "1.0"), Ordering::Greater);
+/// assert_eq!(rpm::rpmvercmp("1.010", "1.9"), Ordering::Greater);
+/// assert_eq!(rpm::rpmvercmp("1.0", "1_0"), Ordering::Equal);
+/// ```
+pub fn rpmvercmp(a: &str, b: &str) -> Ordering {
+    // easy comparison to see if versions are identical
+    if a == b {
+        return Ordering::Equal;
+    }
+
+    let is_separator = |c: u8| !c.is_ascii_alphanumeric() && c != b'~' && c != b'^';
+
+    let mut one = a.as_bytes();
+    let mut two = b.as_bytes();
+
+    // loop through each version segment of both strings and compare them
+    while !one.is_empty() || !two.is_empty() {
+        while one.first().is_some_and(|c| is_separator(*c)) {
+            one = &one[1..];
+        }
+        while two.first().is_some_and(|c| is_separator(*c)) {
+            two = &two[1..];
+        }
+
+        // handle the tilde separator, it sorts before everything else
+        if one.first() == Some(&b'~') || two.first() == Some(&b'~') {
+            if one.first() != Some(&b'~') {
+                return Ordering::Greater;
+            }
+            if two.first() != Some(&b'~') {
+                return Ordering::Less;
+            }
+            one = &one[1..];
+            two = &two[1..];
+            continue;
+        }
+
+        // handle the caret separator. The concept is the same as tilde, except that if one of
+        // the strings ends (base version), the other is considered as the higher version.
+        if one.first() == Some(&b'^') || two.first() == Some(&b'^') {
+            if one.is_empty() {
+                return Ordering::Less;
+            }
+            if two.is_empty() {
+                return Ordering::Greater;
+            }
+            if one.first() != Some(&b'^') {
+                return Ordering::Greater;
+            }
+            if two.first() != Some(&b'^') {
+                return Ordering::Less;
+            }
+            one = &one[1..];
+            two = &two[1..];
+            continue;
+        }
+
+        // if we ran to the end of either, we are finished with the loop
+        if one.is_empty() || two.is_empty() {
+            break;
+        }
+
+        // grab the first completely alpha or completely numeric segment of each
+        let is_num = one[0].is_ascii_digit();
+        let segment_len = |s: &[u8]| {
+            s.iter()
+                .take_while(|c| {
+                    if is_num {
+                        c.is_ascii_digit()
+                    } else {
+                        c.is_ascii_alphabetic()
+                    }
+                })
+                .count()
+        };
+        let (seg_one, rest_one) = one.split_at(segment_len(one));
+        let (seg_two, rest_two) = two.split_at(segment_len(two));
+
+        // the segments are of different types: one numeric, the other alpha (i.e. empty).
+        // numeric segments are always newer than alpha segments
+        if seg_two.is_empty() {
+            return if is_num {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            };
+        }
+
+        let ordering = if is_num {
+            // throw away any leading zeros, whichever number then has more digits wins
+            let seg_one = trim_leading_zeros(seg_one);
+            let seg_two = trim_leading_zeros(seg_two);
+            seg_one
+                .len()
+                .cmp(&seg_two.len())
+                .then_with(|| seg_one.cmp(seg_two))
+        } else {
+            seg_one.cmp(seg_two)
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+
+        one = rest_one;
+        two = rest_two;
+    }
+
+    // this catches the case where all numeric and alpha segments have compared identically
+    // but the segment separating characters were different. Otherwise, whichever version
+    // still has characters left over wins.
+    match (one.is_empty(), two.is_empty()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Less,
+        (false, _) => Ordering::Greater,
+    }
+}
+
+fn trim_leading_zeros(segment: &[u8]) -> &[u8] {
+    let zeros = segment.iter().take_while(|c| **c == b'0').count();
+    &segment[zeros..]
+}
+
+/// The epoch, version and release of a package, e.g. `1:2.0-3.fc39`.
+///
+/// Ordering follows rpm: the epoch is compared first (a missing epoch counts as `0`), then the
+/// version and finally the release, each using [`rpmvercmp`]. A missing release sorts before
+/// any present release.
+///
+/// Because of this, equality is semantic rather than textual - `1.0-1` and `1.00-1` are equal.
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use rpm::Evr;
+///
+/// let old: Evr = "2.0-3.fc39".parse()?;
+/// let new: Evr = "1:1.0-1.fc39".parse()?;
+/// assert!(old < new);
+/// assert_eq!(new.to_string(), "1:1.0-1.fc39");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Evr {
+    /// The package epoch, if any. A missing epoch compares as `0`.
+    pub epoch: Option<u32>,
+    /// The (upstream) version.
+    pub version: String,
+    /// The package release, if any.
+    pub release: Option<String>,
+}
+
+impl Evr {
+    /// Create a new EVR with all components present.
+    pub fn new(epoch: u32, version: impl Into<String>, release: impl Into<String>) -> Self {
+        Evr {
+            epoch: Some(epoch),
+            version: version.into(),
+            release: Some(release.into()),
+        }
+    }
+
+    /// The epoch, defaulting to `0` if not present.
+    pub fn epoch_or_default(&self) -> u32 {
+        self.epoch.unwrap_or(0)
+    }
+
+    /// Compare two EVRs the way rpm does when matching dependency ranges: unlike [`Ord`], the
+    /// release is only taken into account if both sides have one, so `1.0` matches `1.0-3`.
+    pub(crate) fn cmp_for_dependency(&self, other: &Self) -> Ordering {
+        let ordering = self
+            .epoch_or_default()
+            .cmp(&other.epoch_or_default())
+            .then_with(|| rpmvercmp(&self.version, &other.version));
+        match (self.release.as_deref(), other.release.as_deref()) {
+            (Some(a), Some(b)) if !a.is_empty() && !b.is_empty() => {
+                ordering.then_with(|| rpmvercmp(a, b))
+            }
+            _ => ordering,
+        }
+    }
+}
+
+impl Ord for Evr {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.epoch_or_default()
+            .cmp(&other.epoch_or_default())
+            .then_with(|| rpmvercmp(&self.version, &other.version))
+            .then_with(|| {
+                rpmvercmp(
+                    self.release.as_deref().unwrap_or_default(),
+                    other.release.as_deref().unwrap_or_default(),
+                )
+            })
+    }
+}
+
+impl PartialOrd for Evr {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Evr {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Evr {}
+
+impl Display for Evr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(epoch) = self.epoch {
+            write!(f, "{}:", epoch)?;
+        }
+        f.write_str(&self.version)?;
+        if let Some(release) = &self.release {
+            write!(f, "-{}", release)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Evr {
+    type Err = Error;
+
+    /// Parse an EVR string of the form `[epoch:]version[-release]`.
+    ///
+    /// As in rpm, the epoch is the leading run of digits terminated by a `:`, and the release is
+    /// whatever follows the last `-`. An empty epoch (`:1.0`) is treated as `0`.
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let digits = raw.bytes().take_while(u8::is_ascii_digit).count();
+        let (epoch, rest) = match raw[digits..].strip_prefix(':') {
+            Some(rest) if digits == 0 => (Some(0), rest),
+            Some(rest) => {
+                let epoch = raw[..digits]
+                    .parse()
+                    .map_err(|_| Error::InvalidEvr(raw.to_owned()))?;
+                (Some(epoch), rest)
+            }
+            None => (None, raw),
+        };
+        let (version, release) = match rest.rsplit_once('-') {
+            Some((version, release)) => (version, Some(release.to_owned())),
+            None => (rest, None),
+        };
+
+        Ok(Evr {
+            epoch,
+            version: version.to_owned(),
+            release,
+        })
+    }
+}
+
+/// The name, epoch, version, release and architecture identifying a specific package build.
+///
+/// Formatting follows the rpm query tags of the same names, e.g. `Display` produces the same
+/// string as `%{NEVRA}`, and [`Nevra::filename`] the file name rpmbuild would write the package to.
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use rpm::Nevra;
+///
+/// let nevra = Nevra::from_filename("/tmp/foo-bar-1.0-2.fc39.noarch.rpm")?;
+/// assert_eq!(nevra.name, "foo-bar");
+/// assert_eq!(nevra.nvr(), "foo-bar-1.0-2.fc39");
+/// assert_eq!(nevra.filename(), "foo-bar-1.0-2.fc39.noarch.rpm");
+///
+/// let nevra: Nevra = "foo-bar-1:1.0-2.fc39.noarch".parse()?;
+/// assert_eq!(nevra.epoch, Some(1));
+/// assert_eq!(nevra.to_string(), "foo-bar-1:1.0-2.fc39.noarch");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Nevra {
+    /// The package name
+    pub name: String,
+    /// The package epoch, if any
+    pub epoch: Option<u32>,
+    /// The package version
+    pub version: String,
+    /// The package release
+    pub release: String,
+    /// The package architecture, `src` for source packages
+    pub arch: String,
+}
+
+impl Nevra {
+    /// Create a new NEVRA.
+    pub fn new(
+        name: impl Into<String>,
+        epoch: Option<u32>,
+        version: impl Into<String>,
+        release: impl Into<String>,
+        arch: impl Into<String>,
+    ) -> Self {
+        Nevra {
+            name: name.into(),
+            epoch,
+            version: version.into(),
+            release: release.into(),
+            arch: arch.into(),
+        }
+    }
+
+    /// Parse the NEVRA from a package file name of the form `name-version-release.arch.rpm`.
+    ///
+    /// Any leading directories are ignored. File names never include the epoch.
+    pub fn from_filename(filename: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = filename.as_ref();
+        let invalid = || Error::InvalidNevra(path.display().to_string());
+        let file_name = path
+            .file_name()
+            .and_then(|f| f.to_str())
+            .ok_or_else(invalid)?;
+        let nevra = file_name.strip_suffix(".rpm").ok_or_else(invalid)?;
+        nevra.parse().map_err(|_| invalid())
+    }
+
+    /// The epoch, version and release of the package.
+    pub fn evr(&self) -> Evr {
+        Evr {
+            epoch: self.epoch,
+            version: self.version.clone(),
+            release: Some(self.release.clone()),
+        }
+    }
+
+    /// Format as `name-version-release`, like `%{NVR}`.
+    pub fn nvr(&self) -> String {
+        format!("{}-{}-{}", self.name, self.version, self.release)
+    }
+
+    /// Format as `name-[epoch:]version-release`, like `%{NEVR}`.
+    pub fn nevr(&self) -> String {
+        format!("{}-{}", self.name, self.evr())
+    }
+
+    /// Format as `name-[epoch:]version-release.arch`, like `%{NEVRA}`. Same as `to_string()`.
+    pub fn nevra(&self) -> String {
+        self.to_string()
+    }
+
+    /// The canonical file name for the package, `name-version-release.arch.rpm`.
+    pub fn filename(&self) -> String {
+        format!("{}.{}.rpm", self.nvr(), self.arch)
+    }
+}
+
+impl Display for Nevra {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.nevr(), self.arch)
+    }
+}
+
+impl FromStr for Nevra {
+    type Err = Error;
+
+    /// Parse a NEVRA string of the form `name-[epoch:]version-release.arch`.
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidNevra(raw.to_owned());
+        let (nevr, arch) = raw.rsplit_once('.').ok_or_else(invalid)?;
+        let (nev, release) = nevr.rsplit_once('-').ok_or_else(invalid)?;
+        let (name, ev) = nev.rsplit_once('-').ok_or_else(invalid)?;
+        let evr: Evr = ev.parse()?;
+
+        if [name, &evr.version, release, arch]
+            .iter()
+            .any(|s| s.is_empty())
+        {
+            return Err(invalid());
+        }
+
+        Ok(Nevra {
+            name: name.to_owned(),
+            epoch: evr.epoch,
+            version: evr.version,
+            release: release.to_owned(),
+            arch: arch.to_owned(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rpmvercmp() {
+        // taken from the rpm test suite (tests/rpmvercmp.at)
+        let test_table = [
+            ("1.0", "1.0", Ordering::Equal),
+            ("1.0", "2.0", Ordering::Less),
+            ("2.0", "1.0", Ordering::Greater),
+            ("2.0.1", "2.0.1", Ordering::Equal),
+            ("2.0", "2.0.1", Ordering::Less),
+            ("2.0.1", "2.0", Ordering::Greater),
+            ("2.0.1a", "2.0.1a", Ordering::Equal),
+            ("2.0.1a", "2.0.1", Ordering::Greater),
+            ("2.0.1", "2.0.1a", Ordering::Less),
+            ("5.5p1", "5.5p1", Ordering::Equal),
+            ("5.5p1", "5.5p2", Ordering::Less),
+            ("5.5p2", "5.5p1", Ordering::Greater),
+            ("5.5p10", "5.5p10", Ordering::Equal),
+            ("5.5p1", "5.5p10", Ordering::Less),
+            ("5.5p10", "5.5p1", Ordering::Greater),
+            ("10xyz", "10.1xyz", Ordering::Less),
+            ("10.1xyz", "10xyz", Ordering::Greater),
+            ("xyz10", "xyz10", Ordering::Equal),
+            ("xyz10", "xyz10.1", Ordering::Less),
+            ("xyz10.1", "xyz10", Ordering::Greater),
+            ("xyz.4", "xyz.4", Ordering::Equal),
+            ("xyz.4", "8", Ordering::Less),
+            ("8", "xyz.4", Ordering::Greater),
+            ("xyz.4", "2", Ordering::Less),
+            ("2", "xyz.4", Ordering::Greater),
+            ("5.5p2", "5.6p1", Ordering::Less),
+            ("5.6p1", "5.5p2", Ordering::Greater),
+            ("5.6p1", "6.5p1", Ordering::Less),
+            ("6.5p1", "5.6p1", Ordering::Greater),
+            ("6.0.rc1", "6.0", Ordering::Greater),
+            ("6.0", "6.0.rc1", Ordering::Less),
+            ("10b2", "10a1", Ordering::Greater),
+            ("10a2", "10b2", Ordering::Less),
+            ("1.0aa", "1.0aa", Ordering::Equal),
+            ("1.0a", "1.0aa", Ordering::Less),
+            ("1.0aa", "1.0a", Ordering::Greater),
+            ("10.0001", "10.0001", Ordering::Equal),
+            ("10.0001", "10.1", Ordering::Equal),
+            ("10.1", "10.0001", Ordering::Equal),
+            ("10.0001", "10.0039", Ordering::Less),
+            ("10.0039", "10.0001", Ordering::Greater),
+            ("4.999.9", "5.0", Ordering::Less),
+            ("5.0", "4.999.9", Ordering::Greater),
+            ("20101121", "20101121", Ordering::Equal),
+            ("20101121", "20101122", Ordering::Less),
+            ("20101122", "20101121", Ordering::Greater),
+            ("2_0", "2_0", Ordering::Equal),
+            ("2.0", "2_0", Ordering::Equal),
+            ("2_0", "2.0", Ordering::Equal),
+            ("a", "a", Ordering::Equal),
+            ("a+", "a+", Ordering::Equal),
+            ("a+", "a_", Ordering::Equal),
+            ("a_", "a+", Ordering::Equal),
+            ("+a", "+a", Ordering::Equal),
+            ("+a", "_a", Ordering::Equal),
+            ("_a", "+a", Ordering::Equal),
+            ("+_", "+_", Ordering::Equal),
+            ("_+", "+_", Ordering::Equal),
+            ("_+", "_+", Ordering::Equal),
+            ("+", "_", Ordering::Equal),
+            ("_", "+", Ordering::Equal),
+            ("1.0~rc1", "1.0~rc1", Ordering::Equal),
+            ("1.0~rc1", "1.0", Ordering::Less),
+            ("1.0", "1.0~rc1", Ordering::Greater),
+            ("1.0~rc1", "1.0~rc2", Ordering::Less),
+            ("1.0~rc2", "1.0~rc1", Ordering::Greater),
+            ("1.0~rc1~git123", "1.0~rc1~git123", Ordering::Equal),
+            ("1.0~rc1~git123", "1.0~rc1", Ordering::Less),
+            ("1.0~rc1", "1.0~rc1~git123", Ordering::Greater),
+            ("1.0^", "1.0^", Ordering::Equal),
+            ("1.0^", "1.0", Ordering::Greater),
+            ("1.0", "1.0^", Ordering::Less),
+            ("1.0^git1", "1.0^git1", Ordering::Equal),
+            ("1.0^git1", "1.0", Ordering::Greater),
+            ("1.0", "1.0^git1", Ordering::Less),
+            ("1.0^git1", "1.0^git2", Ordering::Less),
+            ("1.0^git2", "1.0^git1", Ordering::Greater),
+            ("1.0^git1", "1.01", Ordering::Less),
+            ("1.01", "1.0^git1", Ordering::Greater),
+            ("1.0^20160101", "1.0^20160101", Ordering::Equal),
+            ("1.0^20160101", "1.0.1", Ordering::Less),
+            ("1.0.1", "1.0^20160101", Ordering::Greater),
+            ("1.0^20160101^git1", "1.0^20160101^git1", Ordering::Equal),
+            ("1.0^20160102", "1.0^20160101^git1", Ordering::Greater),
+            ("1.0^20160101^git1", "1.0^20160102", Ordering::Less),
+            ("1.0~rc1^git1", "1.0~rc1^git1", Ordering::Equal),
+            ("1.0~rc1^git1", "1.0~rc1", Ordering::Greater),
+            ("1.0~rc1", "1.0~rc1^git1", Ordering::Less),
+            ("1.0^git1~pre", "1.0^git1~pre", Ordering::Equal),
+            ("1.0^git1", "1.0^git1~pre", Ordering::Greater),
+            ("1.0^git1~pre", "1.0^git1", Ordering::Less),
+            // numbers too large for any integer type are still handled
+            (
+                "123456789012345678901",
+                "123456789012345678902",
+                Ordering::Less,
+            ),
+        ];
+
+        for (a, b, expected) in test_table {
+            assert_eq!(rpmvercmp(a, b), expected, "comparing {a:?} with {b:?}");
+        }
+    }
+
+    #[test]
+    fn test_evr_parse() -> Result<(), Box<dyn std::error::Error>> {
+        let evr: Evr = "1:2.0-3.fc39".parse()?;
+        assert_eq!(evr.epoch, Some(1));
+        assert_eq!(evr.version, "2.0");
+        assert_eq!(evr.release.as_deref(), Some("3.fc39"));
+        assert_eq!(evr.to_string(), "1:2.0-3.fc39");
+
+        let evr: Evr = "2.0".parse()?;
+        assert_eq!(evr.epoch, None);
+        assert_eq!(evr.version, "2.0");
+        assert_eq!(evr.release, None);
+        assert_eq!(evr.to_string(), "2.0");
+
+        // the release is whatever follows the last dash
+        let evr: Evr = "1.0-beta-2".parse()?;
+        assert_eq!(evr.version, "1.0-beta");
+        assert_eq!(evr.release.as_deref(), Some("2"));
+
+        // an empty epoch is the same as epoch 0
+        let evr: Evr = ":1.0-1".parse()?;
+        assert_eq!(evr.epoch, Some(0));
+
+        // not an epoch, as the leading run isn't purely numeric
+        let evr: Evr = "a1:1.0".parse()?;
+        assert_eq!(evr.epoch, None);
+        assert_eq!(evr.version, "a1:1.0");
+
+        assert!("99999999999:1.0".parse::<Evr>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_evr_ordering() -> Result<(), Box<dyn std::error::Error>> {
+        let parse = |s: &str| s.parse::<Evr>().unwrap();
+
+        assert!(parse("1:1.0-1") > parse("2.0-1"));
+        assert!(parse("0:1.0-1") == parse("1.0-1"));
+        assert!(parse("1.0-2") > parse("1.0-1"));
+        assert!(parse("1.0-1") > parse("1.0"));
+        assert!(parse("1.0-1.fc39") < parse("1.0-1.fc40"));
+        assert!(parse("1.0-1") == parse("1.00-1"));
+
+        let mut evrs = [
+            parse("1.0-1"),
+            parse("1:0.1-1"),
+            parse("1.0~rc1-1"),
+            parse("1.0^git1-1"),
+        ];
+        evrs.sort();
+        let sorted: Vec<String> = evrs.iter().map(|e| e.to_string()).collect();
+        assert_eq!(sorted, ["1.0~rc1-1", "1.0-1", "1.0^git1-1", "1:0.1-1"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_nevra() -> Result<(), Box<dyn std::error::Error>> {
+        let nevra: Nevra = "389-ds-base-devel-1.3.8.4-15.el7.x86_64".parse()?;
+        assert_eq!(
+            nevra,
+            Nevra::new("389-ds-base-devel", None, "1.3.8.4", "15.el7", "x86_64")
+        );
+        assert_eq!(nevra.nvr(), "389-ds-base-devel-1.3.8.4-15.el7");
+        assert_eq!(nevra.nevr(), "389-ds-base-devel-1.3.8.4-15.el7");
+        assert_eq!(nevra.evr().to_string(), "1.3.8.4-15.el7");
+        assert_eq!(
+            nevra.filename(),
+            "389-ds-base-devel-1.3.8.4-15.el7.x86_64.rpm"
+        );
+
+        let nevra = Nevra::new("foo", Some(0), "1.0", "1", "src");
+        assert_eq!(nevra.to_string(), "foo-0:1.0-1.src");
+        assert_eq!(nevra.nevr(), "foo-0:1.0-1");
+        assert_eq!(nevra.filename(), "foo-1.0-1.src.rpm");
+        assert_eq!(nevra.to_string().parse::<Nevra>()?, nevra);
+
+        let nevra = Nevra::from_filename("some/dir/foo-1.0-1.src.rpm")?;
+        assert_eq!(nevra, Nevra::new("foo", None, "1.0", "1", "src"));
+
+        assert!("foo-1.0.x86_64".parse::<Nevra>().is_err());
+        assert!("foo-1.0-1".parse::<Nevra>().is_err());
+        assert!("-1.0-1.x86_64".parse::<Nevra>().is_err());
+        assert!(Nevra::from_filename("foo-1.0-1.x86_64").is_err());
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
 use super::*;
+use digest::Digest;
 use hex_literal::hex;
 
 fn rpm_389_ds_file_path() -> std::path::PathBuf {
@@ -63,13 +64,34 @@ However, it does nothing.",
         .vendor("dummy vendor")
         .url("dummy url")
         .vcs("dummy vcs")
+        .source_rpm("test-1.0.0-1.src.rpm")
+        .dist_url("https://build.example.com/test")
+        .distribution("Example Linux")
+        .disttag("el9")
+        .bug_url("https://bugs.example.com")
+        .platform("x86_64-redhat-linux-gnu")
+        .optflags("-O2 -g")
+        .modularity_label("test:1:1:abcdef")
+        .source_license("MIT AND Apache-2.0")
         .build()?;
 
     pkg.write(&mut buff)?;
 
     // check that generated packages has source rpm tag
     // to be more compatibly recognized as RPM binary packages
-    pkg.metadata.get_source_rpm()?;
+    assert_eq!(pkg.metadata.get_source_rpm()?, "test-1.0.0-1.src.rpm");
+    assert_eq!(pkg.metadata.get_source_nevra()?.nvr(), "test-1.0.0-1");
+    assert_eq!(
+        pkg.metadata.get_dist_url()?,
+        "https://build.example.com/test"
+    );
+    assert_eq!(pkg.metadata.get_distribution()?, "Example Linux");
+    assert_eq!(pkg.metadata.get_disttag()?, "el9");
+    assert_eq!(pkg.metadata.get_bug_url()?, "https://bugs.example.com");
+    assert_eq!(pkg.metadata.get_platform()?, "x86_64-redhat-linux-gnu");
+    assert_eq!(pkg.metadata.get_optflags()?, "-O2 -g");
+    assert_eq!(pkg.metadata.get_modularity_label()?, "test:1:1:abcdef");
+    assert_eq!(pkg.metadata.get_source_license()?, "MIT AND Apache-2.0");
 
     pkg.verify_digests()?;
 
@@ -128,6 +150,165 @@ However, it does nothing.",
     Ok(())
 }
 
+#[test]
+fn test_rpm_source_builder() -> Result<(), Box<dyn std::error::Error>> {
+    let pkg = PackageBuilder::new_source("rpm-empty", "0", "LGPL", "some source package")
+        .release("0")
+        .with_spec_file("./test_assets/fixture_packages/SPECS/rpm-empty.spec")?
+        .with_source_file("./test_assets/awesome.toml")?
+        .no_source("secret.tar.gz")
+        .with_patch_file("./test_assets/awesome.py")?
+        .build_requires(Dependency::greater_eq("gcc", "12"))
+        .build_conflicts(Dependency::any("clang"))
+        .build()?;
+
+    let mut buff = std::io::Cursor::new(Vec::<u8>::new());
+    pkg.write(&mut buff)?;
+    let pkg = Package::parse(&mut std::io::Cursor::new(buff.into_inner()))?;
+    pkg.verify_digests()?;
+
+    let metadata = &pkg.metadata;
+    assert!(metadata.is_source_package());
+    assert!(metadata.get_source_rpm().is_err());
+    assert_eq!(metadata.get_arch()?, "nosrc");
+    assert_eq!(
+        metadata.get_canonical_filename()?,
+        "rpm-empty-0-0.nosrc.rpm"
+    );
+
+    let files: Vec<_> = metadata
+        .get_file_entries()?
+        .into_iter()
+        .map(|f| (f.path.to_string_lossy().into_owned(), f.flags))
+        .collect();
+    assert_eq!(
+        files,
+        [
+            ("awesome.py".to_owned(), FileFlags::empty()),
+            ("awesome.toml".to_owned(), FileFlags::empty()),
+            ("rpm-empty.spec".to_owned(), FileFlags::SPECFILE),
+        ]
+    );
+
+    let header = &metadata.header;
+    assert_eq!(
+        header.get_entry_data_as_string_array(IndexTag::RPMTAG_SOURCE)?,
+        ["awesome.toml", "secret.tar.gz"]
+    );
+    assert_eq!(
+        header.get_entry_data_as_u32_array(IndexTag::RPMTAG_NOSOURCE)?,
+        [1]
+    );
+    assert_eq!(
+        header.get_entry_data_as_string_array(IndexTag::RPMTAG_PATCH)?,
+        ["awesome.py"]
+    );
+    assert!(!header.entry_is_present(IndexTag::RPMTAG_NOPATCH));
+    assert!(header
+        .get_entry_data_as_string(IndexTag::RPMTAG_SPEC)?
+        .starts_with("Name:           rpm-empty"));
+
+    let requires = metadata.get_requires()?;
+    assert!(!requires
+        .iter()
+        .any(|r| r.name == "rpmlib(PayloadFilesHavePrefix)"));
+    assert_eq!(
+        metadata.get_build_requires()?,
+        [Dependency::greater_eq("gcc", "12")]
+    );
+    assert_eq!(metadata.get_build_conflicts()?, [Dependency::any("clang")]);
+
+    assert_eq!(
+        metadata.get_sources()?,
+        [
+            SourceFile {
+                name: "awesome.toml".to_owned(),
+                number: None,
+                excluded: false,
+            },
+            SourceFile {
+                name: "secret.tar.gz".to_owned(),
+                number: Some(1),
+                excluded: true,
+            },
+        ]
+    );
+    assert_eq!(metadata.get_nosource_numbers()?, [1]);
+    assert_eq!(
+        metadata.get_patches()?,
+        [SourceFile {
+            name: "awesome.py".to_owned(),
+            number: None,
+            excluded: false,
+        }]
+    );
+    assert!(metadata.get_nopatch_numbers()?.is_empty());
+    assert_eq!(
+        metadata.get_spec_file_path()?,
+        Some(std::path::PathBuf::from("rpm-empty.spec"))
+    );
+
+    // rpmbuild lists the sources in reverse order of their declaration, and records the
+    // numbers of the `NoSource` ones as in the spec, e.g. for `Source0: awesome.toml`,
+    // `Source5: secret.tar.gz`, `Source6: other.tar.gz` and `NoSource: 5 6`
+    let mut pkg = pkg;
+    let metadata = &mut pkg.metadata;
+    metadata.set_entry(
+        IndexTag::RPMTAG_SOURCE,
+        TagValue::StringArray(vec![
+            "other.tar.gz".to_owned(),
+            "secret.tar.gz".to_owned(),
+            "awesome.toml".to_owned(),
+        ]),
+    )?;
+    metadata.set_entry(IndexTag::RPMTAG_NOSOURCE, TagValue::Int32(vec![6, 5]))?;
+    let excluded: Vec<_> = metadata
+        .get_sources()?
+        .into_iter()
+        .map(|source| (source.name, source.number, source.excluded))
+        .collect();
+    assert_eq!(
+        excluded,
+        [
+            ("other.tar.gz".to_owned(), None, true),
+            ("secret.tar.gz".to_owned(), None, true),
+            ("awesome.toml".to_owned(), None, false),
+        ]
+    );
+    assert_eq!(metadata.get_nosource_numbers()?, [6, 5]);
+
+    // with a single excluded source, its number is known
+    metadata.set_entry(
+        IndexTag::RPMTAG_SOURCE,
+        TagValue::StringArray(vec!["secret.tar.gz".to_owned(), "awesome.toml".to_owned()]),
+    )?;
+    metadata.set_entry(IndexTag::RPMTAG_NOSOURCE, TagValue::Int32(vec![1]))?;
+    let sources = metadata.get_sources()?;
+    assert_eq!((sources[0].number, sources[0].excluded), (Some(1), true));
+    assert_eq!((sources[1].number, sources[1].excluded), (None, false));
+
+    // a source package needs a spec file, and only source packages have sources
+    assert!(matches!(
+        PackageBuilder::new_source("foo", "1.0", "MIT", "foo").build(),
+        Err(Error::InvalidSourcePackage(_))
+    ));
+    assert!(matches!(
+        PackageBuilder::new("foo", "1.0", "MIT", "noarch", "foo")
+            .with_source_file("./test_assets/awesome.toml")?
+            .build(),
+        Err(Error::InvalidSourcePackage(_))
+    ));
+
+    // sources and patches share the top level of the package, so their names must be unique
+    assert!(matches!(
+        PackageBuilder::new_source("foo", "1.0", "MIT", "foo")
+            .with_source_file("./test_assets/awesome.toml")?
+            .with_patch_file("./test_assets/awesome.toml"),
+        Err(Error::InvalidDestinationPath { .. })
+    ));
+    Ok(())
+}
+
 #[test]
 fn test_rpm_header() -> Result<(), Box<dyn std::error::Error>> {
     let rpm_file_path = rpm_389_ds_file_path();
@@ -142,6 +323,10 @@ fn test_rpm_header() -> Result<(), Box<dyn std::error::Error>> {
     assert!(package.metadata.get_epoch().is_err());
     assert_eq!(package.metadata.get_version().unwrap(), "1.3.8.4");
     assert_eq!(package.metadata.get_release().unwrap(), "15.el7");
+    assert_eq!(
+        package.metadata.get_evr().unwrap().to_string(),
+        "1.3.8.4-15.el7"
+    );
     assert_eq!(package.metadata.get_arch().unwrap(), "x86_64");
 
     assert_eq!(
@@ -191,6 +376,10 @@ fn test_rpm_header() -> Result<(), Box<dyn std::error::Error>> {
     );
 
     assert!(!package.metadata.is_source_package());
+    assert_eq!(
+        package.metadata.get_canonical_filename().unwrap(),
+        "389-ds-base-devel-1.3.8.4-15.el7.x86_64.rpm"
+    );
 
     let expected_data = vec![
         (
@@ -435,3 +624,361 @@ fn test_no_rpm_files() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[test]
+fn test_rpm_builder_from_package() -> Result<(), Box<dyn std::error::Error>> {
+    let original = PackageBuilder::new("test", "1.0.0", "MIT", "x86_64", "some package")
+        .epoch(2)
+        .release("3")
+        .compression(CompressionType::Gzip)
+        .with_file(
+            "Cargo.toml",
+            FileOptions::new("/etc/awesome/config.toml").is_config_noreplace(),
+        )?
+        .with_file(
+            "Cargo.toml",
+            FileOptions::new("/usr/bin/awesome")
+                .mode(0o100755)
+                .caps("cap_sys_admin=pe")?
+                .user("hugo"),
+        )?
+        .with_file(
+            "./test_assets/empty_file_for_symlink_create",
+            FileOptions::new("/usr/bin/awesome_link")
+                .mode(0o120644)
+                .symlink("/usr/bin/awesome"),
+        )?
+        .pre_install_script("echo preinst")
+        .verify_script(Scriptlet::new("echo verify").flags(ScriptletFlags::EXPAND))
+        .add_changelog_entry("me", "was awesome, eh?", 1_681_411_811)
+        .requires(Dependency::any("wget"))
+        .provides(Dependency::eq("awesome", "1.0"))
+        .vendor("dummy vendor")
+        .source_rpm("test-1.0.0-3.src.rpm")
+        .build()?;
+
+    let mut original_bytes = Vec::new();
+    original.write(&mut original_bytes)?;
+
+    let rebuilt = PackageBuilder::from_package(&original)?
+        .release("4")
+        .compression(CompressionType::Zstd)
+        .build()?;
+    rebuilt.verify_digests()?;
+
+    let (original, rebuilt) = (&original.metadata, &rebuilt.metadata);
+    assert_eq!(rebuilt.get_release()?, "4");
+    assert_eq!(rebuilt.get_payload_compressor()?, CompressionType::Zstd);
+    assert_eq!(rebuilt.get_epoch()?, 2);
+    for (a, b) in [
+        (original.get_name()?, rebuilt.get_name()?),
+        (original.get_version()?, rebuilt.get_version()?),
+        (original.get_vendor()?, rebuilt.get_vendor()?),
+        (original.get_source_rpm()?, rebuilt.get_source_rpm()?),
+    ] {
+        assert_eq!(a, b);
+    }
+    assert_eq!(rebuilt.get_file_entries()?, original.get_file_entries()?);
+    assert_eq!(rebuilt.get_provides()?, original.get_provides()?);
+    assert_eq!(rebuilt.get_recommends()?, original.get_recommends()?);
+    assert_eq!(
+        rebuilt.get_changelog_entries()?,
+        original.get_changelog_entries()?
+    );
+    assert_eq!(rebuilt.get_pre_install_script()?.script, "echo preinst");
+    let verify = rebuilt.get_verify_script()?;
+    assert_eq!(verify.script, "echo verify");
+    assert_eq!(verify.flags, Some(ScriptletFlags::EXPAND));
+    // the payload requirements are regenerated
+    let requires = rebuilt.get_requires()?;
+    assert!(requires.contains(&Dependency::any("wget")));
+    assert!(requires.contains(&Dependency::rpmlib("PayloadIsZstd", "5.4.18-1")));
+    assert_eq!(requires.len(), original.get_requires()?.len() + 1);
+
+    // rpmio mode letters in the payload flags, like gzip's huffman only strategy, don't matter
+    let mut original = Package::parse(&mut std::io::Cursor::new(original_bytes))?;
+    original
+        .metadata
+        .set_entry(IndexTag::RPMTAG_PAYLOADFLAGS, "9h")?;
+    assert_eq!(
+        original.metadata.get_payload_compression()?,
+        CompressionWithLevel::Gzip(9).into()
+    );
+    let rebuilt = PackageBuilder::from_package(&original)?.build()?;
+    assert_eq!(
+        rebuilt.metadata.get_payload_compressor()?,
+        CompressionType::Gzip
+    );
+
+    // packages built by rpmbuild
+    let original = Package::open(rpm_389_ds_file_path())?;
+    let rebuilt = PackageBuilder::from_package(&original)?.build()?;
+    rebuilt.verify_digests()?;
+    // a package without an epoch doesn't get one
+    assert!(rebuilt.metadata.get_epoch().is_err());
+    let original_files = original.metadata.get_file_entries()?;
+    let rebuilt_files = rebuilt.metadata.get_file_entries()?;
+    assert_eq!(original_files.len(), rebuilt_files.len());
+    for (a, b) in original_files.iter().zip(&rebuilt_files) {
+        assert_eq!(a.path, b.path);
+        assert_eq!(a.mode, b.mode);
+        assert_eq!(a.linkto, b.linkto);
+        if !matches!(a.mode, FileMode::Dir { .. }) {
+            assert_eq!(a.size, b.size);
+        }
+        if let (Some(a), Some(b)) = (&a.digest, &b.digest) {
+            if a.algorithm() == b.algorithm() {
+                assert_eq!(a, b);
+            }
+        }
+    }
+
+    let source = Package::open(
+        cargo_manifest_dir().join("test_assets/fixture_packages/rpm-empty-0-0.src.rpm"),
+    )?;
+    let rebuilt = PackageBuilder::from_package(&source)?.build()?;
+    assert!(rebuilt.metadata.is_source_package());
+    assert_eq!(rebuilt.metadata.get_arch()?, "src");
+    assert_eq!(rebuilt.metadata.get_spec()?, source.metadata.get_spec()?);
+    assert_eq!(
+        rebuilt.metadata.get_spec_file_path()?,
+        source.metadata.get_spec_file_path()?
+    );
+    Ok(())
+}
+
+#[test]
+fn test_rpm_builder_from_package_hardlinks() -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Write;
+
+    let content = std::fs::read(cargo_manifest_dir().join("Cargo.toml"))?;
+    let mut package = PackageBuilder::new("test", "1.0.0", "MIT", "x86_64", "some package")
+        .compression(CompressionType::None)
+        .with_file("Cargo.toml", FileOptions::new("/usr/bin/awesome"))?
+        .with_file("Cargo.toml", FileOptions::new("/usr/bin/awesome_link"))?
+        .build()?;
+
+    // turn the two files into hardlinks, the way rpmbuild archives them: only the last link
+    // carries the content
+    let payload = |files: &[(&str, &[u8])]| -> Result<Vec<u8>, std::io::Error> {
+        let mut archive = Vec::new();
+        for (path, data) in files {
+            let mut writer = cpio::newc::Builder::new(path)
+                .mode(0o100644)
+                .ino(1)
+                .nlink(2)
+                .write(&mut archive, data.len() as u32);
+            writer.write_all(data)?;
+            writer.finish()?;
+        }
+        cpio::newc::trailer(&mut archive)?;
+        Ok(archive)
+    };
+    package
+        .metadata
+        .set_entry(IndexTag::RPMTAG_FILEINODES, TagValue::Int32(vec![1, 1]))?;
+    package.content = payload(&[
+        ("./usr/bin/awesome", &[]),
+        ("./usr/bin/awesome_link", &content),
+    ])?;
+
+    let rebuilt = PackageBuilder::from_package(&package)?.build()?;
+    rebuilt.verify_digests()?;
+    let entries = rebuilt.metadata.get_file_entries()?;
+    assert_eq!(entries.len(), 2);
+    for entry in entries {
+        assert_eq!(entry.size, content.len());
+    }
+
+    // regular files missing from the payload are an error, rather than becoming empty files
+    package.content = payload(&[("./usr/bin/awesome_link", &content)])?;
+    assert!(matches!(
+        PackageBuilder::from_package(&package),
+        Err(Error::MissingPayloadFile(path)) if path == "/usr/bin/awesome"
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_rpm_builder_digest_algorithms() -> Result<(), Box<dyn std::error::Error>> {
+    let content = std::fs::read(cargo_manifest_dir().join("Cargo.toml"))?;
+    for (algorithm, file_digest) in [
+        (
+            DigestAlgorithm::Md5,
+            hex::encode(md5::Md5::digest(&content)),
+        ),
+        (
+            DigestAlgorithm::Sha2_224,
+            hex::encode(sha2::Sha224::digest(&content)),
+        ),
+        (
+            DigestAlgorithm::Sha2_256,
+            hex::encode(sha2::Sha256::digest(&content)),
+        ),
+        (
+            DigestAlgorithm::Sha2_384,
+            hex::encode(sha2::Sha384::digest(&content)),
+        ),
+        (
+            DigestAlgorithm::Sha2_512,
+            hex::encode(sha2::Sha512::digest(&content)),
+        ),
+    ] {
+        let pkg = PackageBuilder::new("test", "1.0.0", "MIT", "noarch", "some package")
+            .file_digest_algorithm(algorithm)
+            .payload_digest_algorithm(algorithm)
+            .with_file(
+                cargo_manifest_dir().join("Cargo.toml"),
+                FileOptions::new("/etc/awesome/config.toml"),
+            )?
+            .build()?;
+        pkg.verify_digests()?;
+
+        let metadata = &pkg.metadata;
+        assert_eq!(metadata.get_file_digest_algorithm()?, algorithm);
+        assert_eq!(metadata.get_payload_digest_algorithm()?, algorithm);
+        let entries = metadata.get_file_entries()?;
+        let digest = entries[0].digest.as_ref().unwrap();
+        assert_eq!(digest.algorithm(), algorithm);
+        assert_eq!(digest.as_hex(), file_digest);
+
+        let requires_file_digests = metadata
+            .get_requires()?
+            .contains(&Dependency::rpmlib("FileDigests", "4.6.0-1"));
+        assert_eq!(requires_file_digests, algorithm != DigestAlgorithm::Md5);
+
+        // a corrupted payload is detected with any algorithm
+        let mut buffer = Vec::new();
+        pkg.write(&mut buffer)?;
+        let last = buffer.len() - 1;
+        buffer[last] ^= 0xff;
+        let corrupted = Package::parse(&mut buffer.as_slice())?;
+        assert!(corrupted.verify_digests().is_err());
+
+        let rebuilt = PackageBuilder::from_package(&pkg)?.build()?;
+        assert_eq!(rebuilt.metadata.get_file_digest_algorithm()?, algorithm);
+        assert_eq!(rebuilt.metadata.get_payload_digest_algorithm()?, algorithm);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_rpm_builder_target() -> Result<(), Box<dyn std::error::Error>> {
+    let builder = || PackageBuilder::new("test", "1.0.0~rc1", "MIT", "noarch", "some package");
+    let requires_feature = |pkg: &Package, feature: &str| -> Result<bool, Error> {
+        Ok(pkg
+            .metadata
+            .get_requires()?
+            .iter()
+            .any(|d| d.name == format!("rpmlib({feature})")))
+    };
+
+    let err = builder()
+        .target(Target::El7)
+        .compression(CompressionType::Zstd)
+        .build()
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        Error::UnsupportedByTarget {
+            target: Target::El7,
+            ..
+        }
+    ));
+    assert_eq!(
+        err.to_string(),
+        "rpmlib(PayloadIsZstd) requires rpm 4.14.0, which is newer than the target EL7 (rpm 4.11.3)"
+    );
+
+    let pkg = builder()
+        .target(Target::El7)
+        .compression(CompressionType::Xz)
+        .build()?;
+    pkg.verify_digests()?;
+    assert!(requires_feature(&pkg, "PayloadIsXz")?);
+    assert!(requires_feature(&pkg, "TildeInVersions")?);
+    assert!(!requires_feature(&pkg, "RichDependencies")?);
+    let header = &pkg.metadata.header;
+    assert!(!header.entry_is_present(IndexTag::RPMTAG_PAYLOADDIGEST));
+    assert!(!header.entry_is_present(IndexTag::RPMTAG_PAYLOADDIGESTALT));
+
+    let rich = Dependency::any("(foo or bar)");
+    assert!(matches!(
+        builder().target(Target::El7).requires(rich.clone()).build(),
+        Err(Error::UnsupportedByTarget { .. })
+    ));
+    let pkg = builder()
+        .target(Target::El8)
+        .requires(rich.clone())
+        .compression(CompressionType::Zstd)
+        .build()?;
+    assert!(requires_feature(&pkg, "RichDependencies")?);
+    assert!(requires_feature(&pkg, "PayloadIsZstd")?);
+    let header = &pkg.metadata.header;
+    assert!(header.entry_is_present(IndexTag::RPMTAG_PAYLOADDIGEST));
+    assert!(!header.entry_is_present(IndexTag::RPMTAG_PAYLOADDIGESTALT));
+
+    assert!(matches!(
+        builder()
+            .target(Target::El9)
+            .pre_untrans_script("echo preuntrans")
+            .build(),
+        Err(Error::UnsupportedByTarget { .. })
+    ));
+    let pkg = builder()
+        .target(Target::Fedora)
+        .requires(Dependency::eq("foo", "1.0^git1"))
+        .pre_untrans_script("echo preuntrans")
+        .build()?;
+    assert!(requires_feature(&pkg, "CaretInVersions")?);
+    assert!(pkg
+        .metadata
+        .header
+        .entry_is_present(IndexTag::RPMTAG_PAYLOADDIGESTALT));
+
+    assert!(matches!(
+        builder()
+            .target(Target::Rpm("4.14.3".parse()?))
+            .requires(Dependency::eq("foo", "1.0^git1"))
+            .build(),
+        Err(Error::UnsupportedByTarget { .. })
+    ));
+    assert_eq!("4.16.1.3".parse::<RpmVersion>()?, RpmVersion::new(4, 16, 1));
+    assert_eq!("4.19".parse::<RpmVersion>()?, RpmVersion::new(4, 19, 0));
+    assert!("4.x".parse::<RpmVersion>().is_err());
+    Ok(())
+}
+
+#[test]
+fn test_refresh_digests_sha3_256() -> Result<(), Box<dyn std::error::Error>> {
+    let mut package = Package::open(rpm_empty_rpm_file_path())?;
+    assert!(!package
+        .metadata
+        .signature
+        .entry_is_present(IndexSignatureTag::RPMSIGTAG_SHA3_256));
+    package.refresh_digests()?;
+    assert!(!package
+        .metadata
+        .signature
+        .entry_is_present(IndexSignatureTag::RPMSIGTAG_SHA3_256));
+
+    // a stale digest, as if the header was edited after rpm 6 added it
+    package.metadata.signature.set_entry(
+        IndexSignatureTag::RPMSIGTAG_SHA3_256,
+        IndexData::StringTag("0".repeat(64)),
+    );
+    assert!(package.verify_digests().is_err());
+    package.refresh_digests()?;
+    package.verify_digests()?;
+
+    let mut header = Vec::new();
+    package.metadata.header.write(&mut header)?;
+    assert_eq!(
+        package
+            .metadata
+            .signature
+            .get_entry_data_as_string(IndexSignatureTag::RPMSIGTAG_SHA3_256)?,
+        hex::encode(sha3::Sha3_256::digest(&header))
+    );
+    Ok(())
+}
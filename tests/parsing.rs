@@ -65,10 +65,15 @@ fn test_package_segment_boundaries() -> Result<(), Box<dyn std::error::Error>> {
             CompressionType::Zstd => &[0x28, 0xb5, 0x2f, 0xfd],
             CompressionType::Xz => &[0xfd, 0x37, 0x7a, 0x58, 0x5a],
             CompressionType::Bzip2 => &[0x42, 0x5a],
+            CompressionType::Lzma => &[0x5d, 0x00, 0x00],
             CompressionType::None => &[0x30, 0x37, 0x30, 0x37, 0x30, 0x31], // CPIO archive magic #
         };
 
         assert!(buf.starts_with(payload_magic));
+        assert_eq!(
+            CompressionType::from_magic(&buf),
+            Some(package.metadata.get_payload_compressor()?)
+        );
 
         Ok(())
     }
@@ -98,3 +103,347 @@ fn test_rpm_file_signatures() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[test]
+fn test_canonical_filenames() -> Result<(), Box<dyn std::error::Error>> {
+    for path in [
+        common::rpm_389_ds_file_path(),
+        common::rpm_empty_path(),
+        common::rpm_empty_source_path(),
+    ] {
+        let metadata = rpm::PackageMetadata::open(&path)?;
+        let filename = path.file_name().unwrap().to_str().unwrap();
+
+        assert_eq!(metadata.get_canonical_filename()?, filename);
+        assert_eq!(
+            metadata.get_nevra()?.nvr(),
+            Nevra::from_filename(&path)?.nvr()
+        );
+    }
+
+    let metadata = rpm::PackageMetadata::open(common::rpm_empty_source_path())?;
+    assert_eq!(metadata.get_nevra()?.arch, "src");
+
+    Ok(())
+}
+
+#[test]
+fn test_source_package_metadata() -> Result<(), Box<dyn std::error::Error>> {
+    let source = rpm::PackageMetadata::open(common::rpm_empty_source_path())?;
+    assert!(source.is_source_package());
+    assert_eq!(
+        source.get_spec_file_path()?,
+        Some(std::path::PathBuf::from("rpm-empty.spec"))
+    );
+    assert!(source
+        .get_spec()?
+        .starts_with("Name:           rpm-empty\n"));
+    assert!(source.get_sources()?.is_empty());
+    assert!(source.get_patches()?.is_empty());
+    assert!(source.get_build_requires()?.is_empty());
+    assert!(source.get_build_conflicts()?.is_empty());
+
+    let binary = rpm::PackageMetadata::open(common::rpm_389_ds_file_path())?;
+    assert_eq!(binary.get_spec_file_path()?, None);
+    assert!(binary.get_build_requires()?.is_empty());
+    let source_nevra = binary.get_source_nevra()?;
+    assert_eq!(source_nevra.nvr(), "389-ds-base-1.3.8.4-15.el7");
+    assert_eq!(source_nevra.arch, "src");
+
+    Ok(())
+}
+
+#[test]
+fn test_edit_header() -> Result<(), Box<dyn std::error::Error>> {
+    let mut package = rpm::Package::open(common::rpm_389_ds_file_path())?;
+    assert!(package
+        .metadata
+        .signature
+        .entry_is_present(IndexSignatureTag::RPMSIGTAG_RSA));
+
+    let metadata = &mut package.metadata;
+    metadata.replace_entry(IndexTag::RPMTAG_RELEASE, "16.el7")?;
+    metadata.set_entry(IndexTag::RPMTAG_VENDOR, "Example Corp")?;
+    metadata.remove_entry(IndexTag::RPMTAG_BUILDHOST)?;
+
+    let mut provides = metadata.get_provides()?;
+    provides.push(Dependency::eq("389-ds-devel", "1.3.8.4"));
+    metadata.set_entry(
+        IndexTag::RPMTAG_PROVIDENAME,
+        TagValue::StringArray(provides.iter().map(|d| d.name.clone()).collect()),
+    )?;
+    metadata.set_entry(
+        IndexTag::RPMTAG_PROVIDEFLAGS,
+        TagValue::Int32(provides.iter().map(|d| d.flags.bits()).collect()),
+    )?;
+    metadata.set_entry(
+        IndexTag::RPMTAG_PROVIDEVERSION,
+        TagValue::StringArray(provides.iter().map(|d| d.version.clone()).collect()),
+    )?;
+
+    assert!(matches!(
+        metadata.replace_entry(IndexTag::RPMTAG_DISTURL, "https://example.com"),
+        Err(Error::TagNotFound(_))
+    ));
+    assert!(matches!(
+        metadata.remove_entry(IndexTag::RPMTAG_HEADERIMMUTABLE),
+        Err(Error::UneditableTag(_))
+    ));
+
+    // the digests no longer match until they are refreshed
+    assert!(package.verify_digests().is_err());
+    package.refresh_digests()?;
+
+    let mut buffer = Vec::new();
+    package.write(&mut buffer)?;
+    let package = rpm::Package::parse(&mut buffer.as_slice())?;
+    package.verify_digests()?;
+
+    let metadata = &package.metadata;
+    assert!(!metadata
+        .signature
+        .entry_is_present(IndexSignatureTag::RPMSIGTAG_RSA));
+    assert_eq!(metadata.get_release()?, "16.el7");
+    assert_eq!(metadata.get_vendor()?, "Example Corp");
+    assert!(matches!(
+        metadata.get_build_host(),
+        Err(Error::TagNotFound(_))
+    ));
+    assert_eq!(metadata.get_provides()?, provides);
+    // untouched entries survive the rewrite
+    assert_eq!(metadata.get_name()?, "389-ds-base-devel");
+    let original = rpm::PackageMetadata::open(common::rpm_389_ds_file_path())?;
+    assert_eq!(metadata.get_file_entries()?, original.get_file_entries()?);
+    assert_eq!(
+        metadata.get_changelog_entries()?,
+        original.get_changelog_entries()?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_refresh_digests_keeps_signature_entries() -> Result<(), Box<dyn std::error::Error>> {
+    let original = rpm::Package::open(common::rpm_ima_signed_file_path())?;
+    let kept_tags = [
+        IndexSignatureTag::RPMSIGTAG_PAYLOADSIZE,
+        IndexSignatureTag::RPMTAG_INSTALLTIME, // RPMSIGTAG_RESERVEDSPACE
+        IndexSignatureTag::RPMSIGTAG_FILESIGNATURES,
+        IndexSignatureTag::RPMSIGTAG_FILESIGNATURE_LENGTH,
+    ];
+    for tag in kept_tags {
+        assert!(original.metadata.signature.entry_is_present(tag));
+    }
+
+    let mut edited = rpm::Package::open(common::rpm_ima_signed_file_path())?;
+    edited
+        .metadata
+        .set_entry(IndexTag::RPMTAG_VENDOR, "Example Corp")?;
+    edited.refresh_digests()?;
+    let mut recompressed = rpm::Package::open(common::rpm_ima_signed_file_path())?;
+    recompressed.recompress(CompressionType::Zstd)?;
+
+    for package in [edited, recompressed] {
+        let mut buffer = Vec::new();
+        package.write(&mut buffer)?;
+        let package = rpm::Package::parse(&mut buffer.as_slice())?;
+        package.verify_digests()?;
+
+        let signature = &package.metadata.signature;
+        for tag in [
+            IndexSignatureTag::RPMSIGTAG_RSA,
+            IndexSignatureTag::RPMSIGTAG_PGP,
+        ] {
+            assert!(!signature.entry_is_present(tag));
+        }
+        for tag in [
+            IndexSignatureTag::RPMSIGTAG_SIZE,
+            IndexSignatureTag::RPMSIGTAG_MD5,
+            IndexSignatureTag::RPMSIGTAG_SHA1,
+            IndexSignatureTag::RPMSIGTAG_SHA256,
+        ] {
+            assert!(signature.entry_is_present(tag));
+        }
+        assert!(!signature.entry_is_present(IndexSignatureTag::RPMSIGTAG_SHA3_256));
+        // entries unrelated to the header and payload digests are kept as they are
+        assert_eq!(
+            signature.get_entry_data_as_u32(IndexSignatureTag::RPMSIGTAG_PAYLOADSIZE)?,
+            original
+                .metadata
+                .signature
+                .get_entry_data_as_u32(IndexSignatureTag::RPMSIGTAG_PAYLOADSIZE)?
+        );
+        for tag in kept_tags {
+            assert!(signature.entry_is_present(tag));
+        }
+        assert_eq!(
+            package.metadata.get_file_entries()?,
+            original.metadata.get_file_entries()?
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_recompress() -> Result<(), Box<dyn std::error::Error>> {
+    let mut uncompressed = rpm::Package::open(common::rpm_389_ds_file_path())?;
+    assert_eq!(
+        uncompressed.metadata.get_payload_compressor()?,
+        CompressionType::Xz
+    );
+    uncompressed.recompress(CompressionType::None)?;
+    uncompressed.verify_digests()?;
+    assert!(uncompressed.content.starts_with(b"070701"));
+
+    for (compression, compression_type) in [
+        (CompressionWithLevel::Zstd(3), CompressionType::Zstd),
+        (CompressionWithLevel::Gzip(6), CompressionType::Gzip),
+        (CompressionWithLevel::Bzip2(9), CompressionType::Bzip2),
+        (CompressionWithLevel::Xz(6), CompressionType::Xz),
+        (CompressionWithLevel::Lzma(6), CompressionType::Lzma),
+    ] {
+        let mut package = rpm::Package::open(common::rpm_389_ds_file_path())?;
+        package.recompress(compression)?;
+
+        let mut buffer = Vec::new();
+        package.write(&mut buffer)?;
+        let mut package = rpm::Package::parse(&mut buffer.as_slice())?;
+        package.verify_digests()?;
+
+        let metadata = &package.metadata;
+        assert_eq!(metadata.get_payload_compressor()?, compression_type);
+        let requires_zstd = metadata
+            .get_requires()?
+            .contains(&Dependency::rpmlib("PayloadIsZstd", "5.4.18-1"));
+        assert_eq!(requires_zstd, compression_type == CompressionType::Zstd);
+        let requires_lzma = metadata
+            .get_requires()?
+            .contains(&Dependency::rpmlib("PayloadIsLzma", "4.4.6-1"));
+        assert_eq!(requires_lzma, compression_type == CompressionType::Lzma);
+        assert_eq!(package.detect_payload_compressor()?, compression_type);
+
+        package.recompress(CompressionType::None)?;
+        assert_eq!(package.content, uncompressed.content);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_recompress_multithreaded() -> Result<(), Box<dyn std::error::Error>> {
+    let mut uncompressed = rpm::Package::open(common::rpm_389_ds_file_path())?;
+    uncompressed.recompress(CompressionType::None)?;
+
+    for (compression, flags) in [
+        (CompressionWithLevel::Zstd(19).threads(4), "19T4"),
+        (CompressionWithLevel::Xz(6).threads(2), "6T2"),
+        (CompressionWithLevel::Gzip(9).threads(4), "9T4"),
+        (CompressionWithLevel::Gzip(1).threads(0), "1T0"),
+        (CompressionWithLevel::Bzip2(9).threads(4), "9"),
+        (
+            CompressionOptions::from(CompressionWithLevel::Zstd(19)).long_distance_matching(),
+            "19L",
+        ),
+        (
+            CompressionWithLevel::Zstd(3).threads(2).window_log(28),
+            "3T2L28",
+        ),
+    ] {
+        let mut package = rpm::Package::open(common::rpm_389_ds_file_path())?;
+        package.recompress(compression)?;
+        package.verify_digests()?;
+        assert_eq!(
+            package
+                .metadata
+                .header
+                .get_entry_data_as_string(IndexTag::RPMTAG_PAYLOADFLAGS)?,
+            flags
+        );
+        if flags.contains('T') {
+            assert_eq!(package.metadata.get_payload_compression()?, compression);
+        }
+
+        package.recompress(CompressionType::None)?;
+        assert_eq!(package.content, uncompressed.content);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_detect_payload_compressor() -> Result<(), Box<dyn std::error::Error>> {
+    let mut package = rpm::Package::open(common::rpm_389_ds_file_path())?;
+    assert_eq!(package.detect_payload_compressor()?, CompressionType::Xz);
+    assert_eq!(
+        package.metadata.get_payload_compression()?,
+        CompressionWithLevel::Xz(2).into()
+    );
+
+    // rpm falls back to gzip if the tag is missing, the payload tells what it really is
+    package
+        .metadata
+        .remove_entry(IndexTag::RPMTAG_PAYLOADCOMPRESSOR)?;
+    assert_eq!(
+        package.metadata.get_payload_compressor()?,
+        CompressionType::None
+    );
+    assert_eq!(package.detect_payload_compressor()?, CompressionType::Xz);
+
+    package
+        .metadata
+        .set_entry(IndexTag::RPMTAG_PAYLOADCOMPRESSOR, "gzip")?;
+    assert_eq!(package.detect_payload_compressor()?, CompressionType::Xz);
+
+    package.recompress(CompressionType::Zstd)?;
+    assert_eq!(
+        package.metadata.get_payload_compressor()?,
+        CompressionType::Zstd
+    );
+    assert_eq!(package.detect_payload_compressor()?, CompressionType::Zstd);
+    Ok(())
+}
+
+#[test]
+fn test_rpmlib_requirements() -> Result<(), Box<dyn std::error::Error>> {
+    let metadata = rpm::PackageMetadata::open(common::rpm_389_ds_file_path())?;
+    let requirements = metadata.get_rpmlib_requirements()?;
+    let features: Vec<_> = requirements
+        .iter()
+        .map(|r| r.feature.map(|f| (f.name, f.introduced_in)))
+        .collect();
+    assert_eq!(
+        features,
+        [
+            Some(("CompressedFileNames", RpmVersion::new(3, 0, 4))),
+            Some(("FileDigests", RpmVersion::new(4, 6, 0))),
+            Some(("PayloadFilesHavePrefix", RpmVersion::new(4, 0, 0))),
+            Some(("PayloadIsXz", RpmVersion::new(4, 7, 0))),
+        ]
+    );
+    assert!(metadata
+        .get_unsupported_rpmlib_requirements(Target::El7)?
+        .is_empty());
+    let unsupported =
+        metadata.get_unsupported_rpmlib_requirements(Target::Rpm(RpmVersion::new(4, 6, 1)))?;
+    assert_eq!(unsupported.len(), 1);
+    assert_eq!(unsupported[0].dependency.name, "rpmlib(PayloadIsXz)");
+
+    let pkg = rpm::PackageBuilder::new("test", "1.0", "MIT", "noarch", "some package")
+        .compression(CompressionType::Zstd)
+        .requires(Dependency::rpmlib("SomethingNew", "5.0.0-1"))
+        .build()?;
+    let unsupported: Vec<_> = pkg
+        .metadata
+        .get_unsupported_rpmlib_requirements(Target::El7)?
+        .into_iter()
+        .map(|r| r.dependency.name)
+        .collect();
+    assert_eq!(
+        unsupported,
+        ["rpmlib(SomethingNew)", "rpmlib(PayloadIsZstd)"]
+    );
+    let unsupported = pkg
+        .metadata
+        .get_unsupported_rpmlib_requirements(Target::Fedora)?;
+    assert_eq!(unsupported.len(), 1);
+    assert!(unsupported[0].feature.is_none());
+    Ok(())
+}
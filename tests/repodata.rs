@@ -0,0 +1,258 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rpm::repodata::*;
+use rpm::*;
+
+mod common;
+
+fn create_repo(name: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let repo_dir = common::cargo_out_dir().join(name);
+    if repo_dir.exists() {
+        fs::remove_dir_all(&repo_dir)?;
+    }
+    fs::create_dir_all(repo_dir.join("Packages"))?;
+    for path in [
+        common::rpm_389_ds_file_path(),
+        common::rpm_empty_path(),
+        common::rpm_empty_source_path(),
+    ] {
+        fs::copy(
+            &path,
+            repo_dir.join("Packages").join(path.file_name().unwrap()),
+        )?;
+    }
+    Ok(repo_dir)
+}
+
+fn repomd_locations(repo_dir: &Path) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let repomd = fs::read_to_string(repo_dir.join("repodata/repomd.xml"))?;
+    Ok(repomd
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("<location href=\""))
+        .map(|href| href.trim_end_matches("\"/>").to_owned())
+        .collect())
+}
+
+#[test]
+fn test_write_repodata() -> Result<(), Box<dyn std::error::Error>> {
+    let repo_dir = create_repo("test_write_repodata")?;
+
+    let builder = RepodataBuilder::from_directory(&repo_dir)?
+        .compression(CompressionType::None)
+        .timestamp(1_681_411_811);
+    let hrefs: Vec<_> = builder
+        .records()
+        .iter()
+        .map(|r| r.location_href.as_str())
+        .collect();
+    assert_eq!(
+        hrefs,
+        [
+            "Packages/389-ds-base-devel-1.3.8.4-15.el7.x86_64.rpm",
+            "Packages/rpm-empty-0-0.src.rpm",
+            "Packages/rpm-empty-0-0.x86_64.rpm",
+        ]
+    );
+    builder.write(&repo_dir)?;
+
+    let repomd = fs::read_to_string(repo_dir.join("repodata/repomd.xml"))?;
+    assert!(repomd.contains("<revision>1681411811</revision>"));
+    assert!(repomd.contains("<data type=\"primary\">"));
+    assert!(repomd.contains("<data type=\"filelists\">"));
+    assert!(repomd.contains("<data type=\"other\">"));
+
+    let locations = repomd_locations(&repo_dir)?;
+    assert_eq!(locations.len(), 3);
+    let primary_path = repo_dir.join(&locations[0]);
+    assert!(locations[0].ends_with("-primary.xml"));
+    let primary = fs::read_to_string(primary_path)?;
+
+    let metadata = PackageMetadata::open(common::rpm_389_ds_file_path())?;
+    let offsets = metadata.get_package_segment_offsets();
+    assert!(primary.contains("<metadata xmlns=\"http://linux.duke.edu/metadata/common\" xmlns:rpm=\"http://linux.duke.edu/metadata/rpm\" packages=\"3\">"));
+    assert!(primary.contains("<name>389-ds-base-devel</name>"));
+    assert!(primary.contains("<version epoch=\"0\" ver=\"1.3.8.4\" rel=\"15.el7\"/>"));
+    assert!(primary.contains(&format!(
+        "<rpm:header-range start=\"{}\" end=\"{}\"/>",
+        offsets.header, offsets.payload
+    )));
+    assert!(primary.contains(
+        "<rpm:entry name=\"389-ds-base-devel\" flags=\"EQ\" epoch=\"0\" ver=\"1.3.8.4\" rel=\"15.el7\"/>"
+    ));
+    assert!(primary.contains("<rpm:sourcerpm>389-ds-base-1.3.8.4-15.el7.src.rpm</rpm:sourcerpm>"));
+    assert!(primary.contains("<location href=\"Packages/rpm-empty-0-0.src.rpm\"/>"));
+    assert!(!primary.contains("rpmlib("));
+
+    let filelists = fs::read_to_string(repo_dir.join(&locations[1]))?;
+    assert!(filelists.contains("<file>/usr/lib64/dirsrv/libsds.so</file>"));
+
+    let other = fs::read_to_string(repo_dir.join(&locations[2]))?;
+    assert!(other.contains("<changelog author="));
+    Ok(())
+}
+
+#[test]
+fn test_write_compressed_repodata() -> Result<(), Box<dyn std::error::Error>> {
+    let repo_dir = create_repo("test_write_compressed_repodata")?;
+
+    let package =
+        PackageBuilder::new("foo", "1.0", "MIT", "noarch", "a <test> & package").build()?;
+    package.write_file(repo_dir.join("Packages/foo-1.0-1.noarch.rpm"))?;
+
+    RepodataBuilder::new()
+        .add_package(&package, "Packages/foo-1.0-1.noarch.rpm")?
+        .write(&repo_dir)?;
+
+    for location in repomd_locations(&repo_dir)? {
+        assert!(location.ends_with(".xml.gz"));
+        let content = fs::read(repo_dir.join(&location))?;
+        let checksum = location
+            .trim_start_matches("repodata/")
+            .split('-')
+            .next()
+            .unwrap();
+        let repomd = fs::read_to_string(repo_dir.join("repodata/repomd.xml"))?;
+        assert!(repomd.contains(&format!("<checksum type=\"sha256\">{checksum}</checksum>")));
+        assert!(repomd.contains(&format!("<size>{}</size>", content.len())));
+    }
+
+    // the record of an in-memory package is the same as that of the written file, apart from the
+    // file time
+    let from_memory = PackageRecord::from_package(&package, "foo.rpm")?;
+    let from_file = PackageRecord::from_package_file(
+        repo_dir.join("Packages/foo-1.0-1.noarch.rpm"),
+        "foo.rpm",
+    )?;
+    assert_eq!(from_memory.checksum, from_file.checksum);
+    assert_eq!(from_memory.package_size, from_file.package_size);
+    assert_eq!(from_memory.summary, "a <test> & package");
+    Ok(())
+}
+
+#[test]
+fn test_read_repodata() -> Result<(), Box<dyn std::error::Error>> {
+    for compression in [
+        CompressionType::None,
+        CompressionType::Gzip,
+        CompressionType::Xz,
+        CompressionType::Zstd,
+        CompressionType::Bzip2,
+        CompressionType::Lzma,
+    ] {
+        let repo_dir = create_repo(&format!("test_read_repodata_{compression:?}"))?;
+        let builder = RepodataBuilder::from_directory(&repo_dir)?
+            .compression(compression)
+            .changelog_limit(usize::MAX)
+            .timestamp(1_681_411_811);
+        builder.write(&repo_dir)?;
+
+        let repodata = Repodata::open(&repo_dir)?;
+        assert_eq!(repodata.repomd.revision, "1681411811");
+        assert_eq!(repodata.repomd.data.len(), 3);
+        assert_eq!(repodata.packages.len(), builder.records().len());
+
+        for (parsed, expected) in repodata.packages.iter().zip(builder.records()) {
+            assert_eq!(parsed.nevra(), expected.nevra());
+            assert_eq!(parsed.checksum, expected.checksum);
+            assert_eq!(parsed.location_href, expected.location_href);
+            assert_eq!(parsed.header_range, expected.header_range);
+            assert_eq!(parsed.description, expected.description);
+            assert_eq!(parsed.files, expected.files);
+            assert_eq!(parsed.changelogs, expected.changelogs);
+
+            let versions = |deps: &[Dependency]| -> Vec<(String, String)> {
+                deps.iter()
+                    .map(|d| (d.name.clone(), d.version.clone()))
+                    .collect()
+            };
+            assert_eq!(versions(&parsed.provides), versions(&expected.provides));
+            assert_eq!(versions(&parsed.requires), versions(&expected.requires));
+        }
+
+        let metadata = PackageMetadata::open(common::rpm_389_ds_file_path())?;
+        let published = &repodata.packages[0];
+        assert_eq!(metadata.get_evr()?, published.evr());
+        // only the flags relevant to dependency resolution are kept in the metadata
+        for provide in metadata.get_provides()? {
+            assert!(provide.is_satisfied_by(&published.provides));
+        }
+    }
+
+    // corrupted metadata is detected
+    let repo_dir = common::cargo_out_dir().join("test_read_repodata_Gzip");
+    let repomd = Repomd::open(&repo_dir)?;
+    let primary = repo_dir.join(&repomd.get_data("primary").unwrap().location_href);
+    let mut content = fs::read(&primary)?;
+    content.push(0);
+    fs::write(&primary, content)?;
+    assert!(matches!(
+        Repodata::open(&repo_dir),
+        Err(Error::RepodataChecksumMismatch { .. })
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_update_repodata() -> Result<(), Box<dyn std::error::Error>> {
+    let repo_dir = create_repo("test_update_repodata")?;
+
+    // without existing metadata, every package is read
+    let builder = RepodataBuilder::update_from_directory(&repo_dir)?;
+    assert_eq!(builder.records().len(), 3);
+
+    // mark the entry of an unchanged package, to tell whether it is reused
+    let records = builder.records().to_vec();
+    let mut builder = RepodataBuilder::new();
+    for mut record in records {
+        if record.name == "389-ds-base-devel" {
+            record.summary = "reused".to_owned();
+        }
+        builder = builder.add_record(record);
+    }
+    builder.write(&repo_dir)?;
+
+    // remove a package, replace another one and add a new one
+    fs::remove_file(repo_dir.join("Packages/rpm-empty-0-0.src.rpm"))?;
+    PackageBuilder::new("rpm-empty", "0", "MIT", "x86_64", "replaced")
+        .build()?
+        .write_file(repo_dir.join("Packages/rpm-empty-0-0.x86_64.rpm"))?;
+    PackageBuilder::new("foo", "1.0", "MIT", "noarch", "new")
+        .build()?
+        .write_file(repo_dir.join("Packages/foo-1.0-1.noarch.rpm"))?;
+
+    RepodataBuilder::update_from_directory(&repo_dir)?.write(&repo_dir)?;
+    let repodata = Repodata::open(&repo_dir)?;
+    let summaries: Vec<_> = repodata
+        .packages
+        .iter()
+        .map(|r| (r.location_href.as_str(), r.summary.as_str()))
+        .collect();
+    assert_eq!(
+        summaries,
+        [
+            (
+                "Packages/389-ds-base-devel-1.3.8.4-15.el7.x86_64.rpm",
+                "reused"
+            ),
+            ("Packages/foo-1.0-1.noarch.rpm", "new"),
+            ("Packages/rpm-empty-0-0.x86_64.rpm", "replaced"),
+        ]
+    );
+    let reused = &repodata.packages[0];
+    assert!(!reused.files.is_empty());
+    assert!(!reused.changelogs.is_empty());
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_repodata_symlink_loop() -> Result<(), Box<dyn std::error::Error>> {
+    let repo_dir = create_repo("test_repodata_symlink_loop")?;
+    // symlinked directories are not followed, so a link to an ancestor doesn't loop
+    std::os::unix::fs::symlink("..", repo_dir.join("Packages/latest"))?;
+
+    let builder = RepodataBuilder::from_directory(&repo_dir)?;
+    assert_eq!(builder.records().len(), 3);
+    Ok(())
+}
@@ -4,7 +4,7 @@ use chrono::TimeZone;
 
 use rpm::{
     self,
-    signature::pgp::{Signer, Verifier},
+    signature::pgp::{Keyring, Signer, Verifier},
 };
 
 mod common;
@@ -114,6 +114,426 @@ fn test_verify_package_with_wrong_key_type() -> Result<(), Box<dyn std::error::E
     Ok(())
 }
 
+/// Test signing a package with several keys, and verifying it with any of them
+#[test]
+fn test_verify_package_with_multiple_signatures() -> Result<(), Box<dyn std::error::Error>> {
+    let rsa_signer = Signer::load_from_asc_bytes(&common::rsa_private_key())?;
+    let eddsa_signer = Signer::load_from_asc_bytes(&common::eddsa_private_key())?;
+
+    let mut pkg = rpm::PackageBuilder::new("foo", "1.0.0", "MIT", "x86_64", "an empty package")
+        .build_and_sign(&rsa_signer)?;
+    pkg.add_signature_with_timestamp(&eddsa_signer, 1_600_000_000)?;
+
+    let out_file = common::cargo_out_dir().join("multiple_signatures_pkg.rpm");
+    pkg.write_file(&out_file)?;
+    let pkg = rpm::Package::open(&out_file)?;
+
+    let signature = &pkg.metadata.signature;
+    // the existing RSA signature is carried over to the rpm 6 tag
+    assert_eq!(
+        signature
+            .get_entry_data_as_string_array(rpm::IndexSignatureTag::RPMSIGTAG_OPENPGP)?
+            .len(),
+        2
+    );
+    assert!(signature.entry_is_present(rpm::IndexSignatureTag::RPMSIGTAG_RSA));
+    assert!(signature.entry_is_present(rpm::IndexSignatureTag::RPMSIGTAG_PGP));
+    assert!(!signature.entry_is_present(rpm::IndexSignatureTag::RPMSIGTAG_DSA));
+    assert!(signature.entry_is_present(rpm::IndexSignatureTag::RPMSIGTAG_SHA3_256));
+    pkg.verify_digests()?;
+
+    pkg.verify_signature(Verifier::load_from_asc_bytes(&common::rsa_public_key())?)?;
+    pkg.verify_signature(Verifier::load_from_asc_bytes(&common::eddsa_public_key())?)?;
+
+    let (_, other_key) = common::load_protected_rsa_keys();
+    assert!(matches!(
+        pkg.verify_signature(Verifier::load_from_asc_bytes(&other_key)?),
+        Err(rpm::Error::KeyNotFoundError { key_ref: _ })
+    ));
+
+    // an unsigned package gets the first signature in the legacy tags as well
+    let mut pkg =
+        rpm::PackageBuilder::new("foo", "1.0.0", "MIT", "x86_64", "an empty package").build()?;
+    pkg.add_signature_with_timestamp(&eddsa_signer, 1_600_000_000)?;
+    pkg.add_signature_with_timestamp(&rsa_signer, 1_600_000_000)?;
+
+    let signature = &pkg.metadata.signature;
+    assert_eq!(
+        signature
+            .get_entry_data_as_string_array(rpm::IndexSignatureTag::RPMSIGTAG_OPENPGP)?
+            .len(),
+        2
+    );
+    assert!(signature.entry_is_present(rpm::IndexSignatureTag::RPMSIGTAG_DSA));
+    assert!(!signature.entry_is_present(rpm::IndexSignatureTag::RPMSIGTAG_RSA));
+    pkg.verify_signature(Verifier::load_from_asc_bytes(&common::rsa_public_key())?)?;
+    pkg.verify_signature(Verifier::load_from_asc_bytes(&common::eddsa_public_key())?)?;
+
+    Ok(())
+}
+
+/// Test removing the signatures of a package, leaving everything else as is
+#[test]
+fn test_remove_signatures() -> Result<(), Box<dyn std::error::Error>> {
+    let signer = Signer::load_from_asc_bytes(&common::rsa_private_key())?;
+    let other_signer = Signer::load_from_asc_bytes(&common::eddsa_private_key())?;
+    let verifier = Verifier::load_from_asc_bytes(&common::rsa_public_key())?;
+
+    let pkg =
+        rpm::PackageBuilder::new("foo", "1.0.0", "MIT", "x86_64", "an empty package").build()?;
+    let mut unsigned = Vec::new();
+    pkg.write(&mut unsigned)?;
+
+    // signed packages differ only by their signatures
+    let mut pkg = rpm::Package::parse(&mut unsigned.as_slice())?;
+    pkg.sign_with_timestamp(&signer, 1_600_000_000)?;
+    pkg.verify_signature(&verifier)?;
+    pkg.remove_signatures()?;
+    let mut delsigned = Vec::new();
+    pkg.write(&mut delsigned)?;
+    assert_eq!(unsigned, delsigned);
+
+    let pkg = rpm::Package::parse(&mut delsigned.as_slice())?;
+    pkg.verify_digests()?;
+    assert!(matches!(
+        pkg.verify_signature(&verifier),
+        Err(rpm::Error::NoSignatureFound)
+    ));
+
+    // the rpm 6 signatures are removed as well, while the SHA3-256 digest is kept
+    let mut pkg = rpm::Package::parse(&mut unsigned.as_slice())?;
+    pkg.add_signature_with_timestamp(&signer, 1_600_000_000)?;
+    pkg.add_signature_with_timestamp(&other_signer, 1_600_000_000)?;
+    pkg.remove_signatures()?;
+    let signature = &pkg.metadata.signature;
+    assert!(!signature.entry_is_present(rpm::IndexSignatureTag::RPMSIGTAG_OPENPGP));
+    assert!(!signature.entry_is_present(rpm::IndexSignatureTag::RPMSIGTAG_RSA));
+    assert!(!signature.entry_is_present(rpm::IndexSignatureTag::RPMSIGTAG_DSA));
+    assert!(signature.entry_is_present(rpm::IndexSignatureTag::RPMSIGTAG_SHA3_256));
+    pkg.verify_digests()?;
+
+    // an externally signed package keeps its header and payload
+    let pkg_path = common::rpm_ima_signed_file_path();
+    let original = std::fs::read(&pkg_path)?;
+    let mut pkg = rpm::Package::open(&pkg_path)?;
+    assert!(pkg
+        .metadata
+        .signature
+        .entry_is_present(rpm::IndexSignatureTag::RPMSIGTAG_RSA));
+    pkg.remove_signatures()?;
+    for tag in [
+        rpm::IndexSignatureTag::RPMSIGTAG_RSA,
+        rpm::IndexSignatureTag::RPMSIGTAG_PGP,
+    ] {
+        assert!(!pkg.metadata.signature.entry_is_present(tag));
+    }
+    for tag in [
+        rpm::IndexSignatureTag::RPMSIGTAG_MD5,
+        rpm::IndexSignatureTag::RPMSIGTAG_SHA1,
+        rpm::IndexSignatureTag::RPMSIGTAG_SHA256,
+        rpm::IndexSignatureTag::RPMSIGTAG_SIZE,
+    ] {
+        assert!(pkg.metadata.signature.entry_is_present(tag));
+    }
+    pkg.verify_digests()?;
+
+    let mut delsigned = Vec::new();
+    pkg.write(&mut delsigned)?;
+    let offsets = pkg.metadata.get_package_segment_offsets();
+    let original_offsets = rpm::Package::open(&pkg_path)?
+        .metadata
+        .get_package_segment_offsets();
+    assert_eq!(
+        &delsigned[offsets.header as usize..],
+        &original[original_offsets.header as usize..]
+    );
+
+    Ok(())
+}
+
+/// Test reporting on the digests and signatures of a package
+#[test]
+fn test_verification_report() -> Result<(), Box<dyn std::error::Error>> {
+    use rpm::{
+        signature::pgp::{HashAlgorithm, PublicKeyAlgorithm},
+        DigestKind, IndexSignatureTag, VerificationStatus,
+    };
+
+    let signer = Signer::load_from_asc_bytes(&common::rsa_private_key())?;
+    let verifier = Verifier::load_from_asc_bytes(&common::rsa_public_key())?;
+    let other_verifier = Verifier::load_from_asc_bytes(&common::eddsa_public_key())?;
+
+    let mut pkg =
+        rpm::PackageBuilder::new("foo", "1.0.0", "MIT", "x86_64", "an empty package").build()?;
+
+    let report = pkg.verification_report(&verifier)?;
+    assert!(!report.is_signed());
+    assert!(!report.is_verified());
+    assert!(!report.has_bad());
+    assert!(!report.is_md5_only());
+    assert!(report
+        .signatures
+        .iter()
+        .all(|signature| signature.status == VerificationStatus::NotPresent));
+    let digests: Vec<_> = report
+        .digests
+        .iter()
+        .map(|digest| (digest.kind, digest.status))
+        .collect();
+    assert_eq!(
+        digests,
+        [
+            (DigestKind::HeaderSha3_256, VerificationStatus::NotPresent),
+            (DigestKind::HeaderSha256, VerificationStatus::Verified),
+            (DigestKind::HeaderSha1, VerificationStatus::Verified),
+            (
+                DigestKind::Payload(Some(rpm::DigestAlgorithm::Sha2_256)),
+                VerificationStatus::Verified
+            ),
+            (DigestKind::Md5, VerificationStatus::Verified),
+        ]
+    );
+    assert_eq!(pkg.check_digests()?, report.digests);
+
+    pkg.sign_with_timestamp(&signer, 1_600_000_000)?;
+    let report = pkg.verification_report(&verifier)?;
+    assert!(report.is_signed());
+    assert!(report.is_verified());
+    let present: Vec<_> = report
+        .signatures
+        .iter()
+        .filter(|signature| signature.status.is_present())
+        .collect();
+    assert_eq!(present.len(), 2);
+    for (signature, tag) in present.iter().zip([
+        IndexSignatureTag::RPMSIGTAG_RSA,
+        IndexSignatureTag::RPMSIGTAG_PGP,
+    ]) {
+        assert_eq!(signature.tag, tag);
+        assert_eq!(signature.status, VerificationStatus::Verified);
+        let info = signature.info.as_ref().unwrap();
+        assert_eq!(info.tag, tag);
+        assert_eq!(info.public_key_algorithm, PublicKeyAlgorithm::RSA);
+        assert_eq!(info.hash_algorithm, HashAlgorithm::SHA2_256);
+        assert_eq!(info.created, Some(rpm::Timestamp(1_600_000_000)));
+        assert!(info.key_id.is_some());
+    }
+    let key_id = present[0].info.as_ref().unwrap().key_id.clone().unwrap();
+    let output = report.to_string();
+    assert!(output.starts_with(&format!(
+        "    Header V4 RSA/SHA256 Signature, key ID {key_id}: OK\n    Header SHA256 digest: OK\n"
+    )));
+    assert!(output.ends_with(&format!(
+        "    V4 RSA/SHA256 Signature, key ID {key_id}: OK\n    MD5 digest: OK\n"
+    )));
+
+    // signatures made by other keys are reported rather than failing the check
+    let report = pkg.verification_report(&other_verifier)?;
+    assert!(report.is_signed());
+    assert!(!report.is_verified());
+    assert!(!report.has_bad());
+    assert!(report
+        .signatures
+        .iter()
+        .filter(|signature| signature.status.is_present())
+        .all(|signature| signature.status == VerificationStatus::NoKey));
+
+    // a modified payload is reported as bad
+    pkg.content.push(0);
+    let report = pkg.verification_report(&verifier)?;
+    assert!(report.has_bad());
+    assert!(!report.is_verified());
+    let bad: Vec<_> = report
+        .digests
+        .iter()
+        .filter(|digest| digest.status == VerificationStatus::Bad)
+        .map(|digest| digest.kind)
+        .collect();
+    assert_eq!(
+        bad,
+        [
+            DigestKind::Payload(Some(rpm::DigestAlgorithm::Sha2_256)),
+            DigestKind::Md5
+        ]
+    );
+    assert!(report.signatures.iter().any(|signature| signature.tag
+        == IndexSignatureTag::RPMSIGTAG_PGP
+        && signature.status == VerificationStatus::Bad));
+
+    Ok(())
+}
+
+/// Test listing the details of all signatures of a package
+#[test]
+fn test_signature_details() -> Result<(), Box<dyn std::error::Error>> {
+    use rpm::{
+        signature::pgp::{HashAlgorithm, PublicKeyAlgorithm},
+        IndexSignatureTag,
+    };
+
+    let rsa_signer = Signer::load_from_asc_bytes(&common::rsa_private_key())?;
+    let eddsa_signer = Signer::load_from_asc_bytes(&common::eddsa_private_key())?;
+
+    let mut pkg =
+        rpm::PackageBuilder::new("foo", "1.0.0", "MIT", "x86_64", "an empty package").build()?;
+    assert!(pkg.signatures().is_empty());
+    assert!(matches!(
+        pkg.signature_key_ids(),
+        Err(rpm::Error::NoSignatureFound)
+    ));
+
+    pkg.sign_with_timestamp(&rsa_signer, 1_600_000_000)?;
+    pkg.add_signature_with_timestamp(&eddsa_signer, 1_700_000_000)?;
+
+    // the copy of the legacy signature in the rpm 6 tag is only listed once
+    let signatures = pkg
+        .signatures()
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+    let tags: Vec<_> = signatures.iter().map(|signature| signature.tag).collect();
+    assert_eq!(
+        tags,
+        [
+            IndexSignatureTag::RPMSIGTAG_RSA,
+            IndexSignatureTag::RPMSIGTAG_OPENPGP,
+            IndexSignatureTag::RPMSIGTAG_PGP,
+        ]
+    );
+
+    let rsa = &signatures[0];
+    assert_eq!(rsa.public_key_algorithm, PublicKeyAlgorithm::RSA);
+    assert_eq!(rsa.hash_algorithm, HashAlgorithm::SHA2_256);
+    assert_eq!(rsa.created, Some(rpm::Timestamp(1_600_000_000)));
+    let eddsa = &signatures[1];
+    assert_eq!(eddsa.public_key_algorithm, PublicKeyAlgorithm::EdDSA);
+    assert_eq!(eddsa.created, Some(rpm::Timestamp(1_700_000_000)));
+    assert_ne!(rsa.key_id, eddsa.key_id);
+
+    assert_eq!(
+        pkg.signature_key_ids()?,
+        [rsa.key_id.clone().unwrap(), eddsa.key_id.clone().unwrap()]
+    );
+
+    // a corrupted signature doesn't hide the other ones
+    let mut bytes = Vec::new();
+    pkg.write(&mut bytes)?;
+    let encoded = pkg
+        .metadata
+        .signature
+        .get_entry_data_as_string_array(IndexSignatureTag::RPMSIGTAG_OPENPGP)?
+        .last()
+        .unwrap()
+        .clone();
+    let start = bytes
+        .windows(encoded.len())
+        .position(|window| window == encoded.as_bytes())
+        .unwrap();
+    bytes[start..start + 4].copy_from_slice(b"!!!!");
+    let corrupted = rpm::Package::parse(&mut bytes.as_slice())?;
+    let signatures = corrupted.signatures();
+    assert_eq!(signatures.len(), 3);
+    assert_eq!(signatures[0].as_ref().unwrap(), rsa);
+    assert!(signatures[1].is_err());
+    assert!(signatures[2].is_ok());
+
+    // signatures made by rpmsign, which carry the fingerprint of the issuer
+    let pkg = rpm::Package::open(common::rpm_ima_signed_file_path())?;
+    let signatures = pkg
+        .signatures()
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+    assert!(!signatures.is_empty());
+    for signature in &signatures {
+        assert_eq!(signature.public_key_algorithm, PublicKeyAlgorithm::RSA);
+        // a V4 key id is the tail of the fingerprint
+        let fingerprint = signature.fingerprint.as_ref().unwrap();
+        assert_eq!(fingerprint.len(), 40);
+        assert!(fingerprint.ends_with(signature.key_id.as_ref().unwrap()));
+    }
+
+    Ok(())
+}
+
+/// Test verifying packages with a keyring holding several keys
+#[test]
+fn test_verify_with_keyring() -> Result<(), Box<dyn std::error::Error>> {
+    let mut keyring = Keyring::new();
+    // holds both public and secret keys, the latter are skipped
+    keyring.add_from_dir(
+        common::cargo_manifest_dir().join("test_assets/fixture_packages/signing_keys"),
+    )?;
+    assert_eq!(keyring.len(), 3);
+
+    let build = |signer: &Signer| {
+        rpm::PackageBuilder::new("foo", "1.0.0", "MIT", "x86_64", "an empty package")
+            .build_and_sign(signer)
+    };
+    let (rsa_key, _) = common::load_rsa_keys();
+    let (protected_key, _) = common::load_protected_rsa_keys();
+    let (eddsa_key, _) = common::load_eddsa_keys();
+    let (other_key, _) = common::load_asc_keys();
+    let protected_signer = Signer::load_from_asc_bytes(&protected_key)?
+        .with_key_passphrase(common::test_protected_private_key_passphrase());
+    for signer in [
+        Signer::load_from_asc_bytes(&rsa_key)?,
+        protected_signer,
+        Signer::load_from_asc_bytes(&eddsa_key)?,
+    ] {
+        build(&signer)?.verify_signature(&keyring)?;
+    }
+
+    let pkg = build(&Signer::load_from_asc_bytes(&other_key)?)?;
+    assert!(matches!(
+        pkg.verify_signature(&keyring),
+        Err(rpm::Error::KeyNotFoundError { key_ref: _ })
+    ));
+    let report = pkg.verification_report(&keyring)?;
+    assert!(report.is_signed() && !report.is_verified() && !report.has_bad());
+
+    // binary keys, as exported by `gpg --export`
+    let key_id = pkg.signature_key_ids()?.remove(0);
+    assert!(!keyring.contains(&key_id));
+    keyring.add_from_file(common::cargo_manifest_dir().join("test_assets/public_key.gpg"))?;
+    assert_eq!(keyring.len(), 4);
+    assert!(keyring.contains(&key_id));
+    pkg.verify_signature(&keyring)?;
+
+    // an armored file holding several keys
+    let mut armored = common::rsa_public_key();
+    armored.extend(common::eddsa_public_key());
+    let mut keyring = Keyring::new();
+    keyring.add_from_bytes(&armored)?;
+    assert_eq!(keyring.len(), 2);
+
+    // data which doesn't hold a public key is an error, rather than being silently skipped
+    let signature = b"-----BEGIN PGP SIGNATURE-----\n\niQ==\n-----END PGP SIGNATURE-----\n";
+    let truncated = &common::rsa_public_key()[..200];
+    for input in [&signature[..], truncated, b"not a key", b""] {
+        assert!(keyring.add_from_bytes(input).is_err());
+    }
+    assert_eq!(keyring.len(), 2);
+
+    // a directory holding a file which isn't a key fails to load as a whole
+    let dir = common::cargo_out_dir().join("keyring_with_other_file");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(dir.join("RPM-GPG-KEY-rsa"), common::rsa_public_key())?;
+    std::fs::write(dir.join("RPM-GPG-KEY-secret"), common::rsa_private_key())?;
+    let mut keyring = Keyring::new();
+    keyring.add_from_dir(&dir)?;
+    assert_eq!(keyring.len(), 1);
+
+    std::fs::copy(
+        common::cargo_manifest_dir().join("test_assets/awesome.toml"),
+        dir.join("README"),
+    )?;
+    let mut keyring = Keyring::new();
+    assert!(keyring.add_from_dir(&dir).is_err());
+    assert!(keyring.is_empty());
+
+    Ok(())
+}
+
 #[track_caller]
 fn resign_and_verify_with_keys(
     pkg_path: &Path,